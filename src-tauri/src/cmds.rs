@@ -6,7 +6,7 @@ use crate::{
     utils::{dirs, help, resolve, tmpl},
 };
 use crate::{ret_err, wrap_err};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use backup::WebDav;
 use reqwest_dav::list_cmd::ListFile;
 use serde::{Deserialize, Serialize};
@@ -55,9 +55,21 @@ pub async fn enhance_profiles() -> CmdResult {
 }
 
 #[tauri::command]
-pub async fn import_profile(url: String, option: Option<PrfOption>) -> CmdResult {
+pub async fn import_profile(
+    url: String,
+    option: Option<PrfOption>,
+    duplicate: Option<DuplicateMode>,
+) -> CmdResult<String> {
     let item = wrap_err!(PrfItem::from_url(&url, None, None, option).await)?;
-    wrap_err!(Config::profiles().data().append_item(item))
+    wrap_err!(feat::import_profile(item, duplicate.unwrap_or_default()).await)
+}
+
+#[tauri::command]
+pub fn find_profiles_by_url(url: String) -> CmdResult<Vec<PrfItem>> {
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    let items = profiles.find_items_by_url(&url);
+    Ok(items.into_iter().cloned().collect())
 }
 
 #[tauri::command]
@@ -66,9 +78,13 @@ pub async fn reorder_profile(active_id: String, over_id: String) -> CmdResult {
 }
 
 #[tauri::command]
-pub async fn create_profile(item: PrfItem, file_data: Option<String>) -> CmdResult {
+pub async fn create_profile(
+    item: PrfItem,
+    file_data: Option<String>,
+    duplicate: Option<DuplicateMode>,
+) -> CmdResult<String> {
     let item = wrap_err!(PrfItem::from(item, file_data).await)?;
-    wrap_err!(Config::profiles().data().append_item(item))
+    wrap_err!(feat::import_profile(item, duplicate.unwrap_or_default()).await)
 }
 
 #[tauri::command]
@@ -171,6 +187,71 @@ pub fn save_profile_file(index: String, file_data: Option<String>) -> CmdResult
     wrap_err!(item.save_file(file_data.unwrap()))
 }
 
+#[tauri::command]
+pub fn validate_rule(rule: String) -> CmdResult<IRule> {
+    wrap_err!(IRule::parse(&rule))
+}
+
+#[tauri::command]
+pub fn get_merge_rules(index: String, section: String) -> CmdResult<Vec<IRuleEntry>> {
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    let item = wrap_err!(profiles.get_item(&index))?;
+    let rules = wrap_err!(item.get_merge_rules(&section))?;
+    Ok(rules.iter().map(|rule| IRuleEntry::parse(rule)).collect())
+}
+
+/// insert the rule at `position`, append to the end if it's none
+#[tauri::command]
+pub fn add_merge_rule(
+    index: String,
+    section: String,
+    rule: String,
+    position: Option<usize>,
+) -> CmdResult {
+    let rule = wrap_err!(IRule::parse(&rule))?;
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    let item = wrap_err!(profiles.get_item(&index))?;
+    wrap_err!(item.edit_merge_rules(&section, |rules| {
+        let position = position.unwrap_or(rules.len());
+        if position > rules.len() {
+            bail!("rule position {position} out of range");
+        }
+        rules.insert(position, rule.to_string());
+        Ok(())
+    }))
+}
+
+#[tauri::command]
+pub fn move_merge_rule(index: String, section: String, from: usize, to: usize) -> CmdResult {
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    let item = wrap_err!(profiles.get_item(&index))?;
+    wrap_err!(item.edit_merge_rules(&section, |rules| {
+        if from >= rules.len() || to >= rules.len() {
+            bail!("rule position out of range");
+        }
+        let rule = rules.remove(from);
+        rules.insert(to, rule);
+        Ok(())
+    }))
+}
+
+#[tauri::command]
+pub fn delete_merge_rule(index: String, section: String, position: usize) -> CmdResult {
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    let item = wrap_err!(profiles.get_item(&index))?;
+    wrap_err!(item.edit_merge_rules(&section, |rules| {
+        if position >= rules.len() {
+            bail!("rule position {position} out of range");
+        }
+        rules.remove(position);
+        Ok(())
+    }))
+}
+
 #[tauri::command]
 pub fn get_clash_info() -> CmdResult<ClashInfo> {
     Ok(Config::clash().latest().get_client_info())
@@ -242,12 +323,68 @@ pub async fn change_clash_core(clash_core: Option<String>) -> CmdResult {
     wrap_err!(CoreManager::global().change_core(clash_core).await)
 }
 
+/// the builtin cores and the cores added by the user
+#[tauri::command]
+pub fn get_core_list() -> CmdResult<Vec<binary::CoreInfo>> {
+    Ok(binary::list_cores())
+}
+
+/// add a core binary provided by the user
+#[tauri::command]
+pub async fn register_core(name: String, path: String) -> CmdResult<IVergeCore> {
+    wrap_err!(feat::register_core(name, path).await)
+}
+
+#[tauri::command]
+pub async fn unregister_core(name: String) -> CmdResult {
+    wrap_err!(feat::unregister_core(name).await)
+}
+
+/// the core instances in `core_instances` and whether they are running
+#[tauri::command]
+pub fn get_core_instances() -> CmdResult<Vec<instance::InstanceStatus>> {
+    Ok(CoreManager::global().instance_status())
+}
+
+#[tauri::command]
+pub async fn start_core_instance(name: String) -> CmdResult {
+    wrap_err!(CoreManager::global().start_instance(&name).await)
+}
+
+#[tauri::command]
+pub fn stop_core_instance(name: String) -> CmdResult {
+    wrap_err!(CoreManager::global().stop_instance(&name))
+}
+
+/// download and verify the core, then replace the builtin sidecar
+/// use `core_update_url` if the url is none
+#[tauri::command]
+pub async fn update_core(
+    url: Option<String>,
+    sha256: Option<String>,
+) -> CmdResult<binary::CoreVersion> {
+    wrap_err!(updater::CoreUpdater::global().update(url, sha256).await)
+}
+
+/// restore the core before the last update
+#[tauri::command]
+pub async fn rollback_core() -> CmdResult {
+    wrap_err!(updater::CoreUpdater::global().rollback().await)
+}
+
 /// restart the sidecar
 #[tauri::command]
 pub async fn restart_sidecar() -> CmdResult {
+    supervisor::Supervisor::global().reset();
     wrap_err!(CoreManager::global().run_core().await)
 }
 
+/// the state of the core process and the last crash
+#[tauri::command]
+pub fn get_core_status() -> CmdResult<supervisor::SupervisorStatus> {
+    Ok(supervisor::Supervisor::global().status())
+}
+
 #[tauri::command]
 pub fn grant_permission(_core: String) -> CmdResult {
     #[cfg(any(target_os = "macos", target_os = "linux"))]
@@ -284,9 +421,28 @@ pub fn get_auto_proxy() -> CmdResult<Mapping> {
     Ok(map)
 }
 
+/// the logs of the main core, or the core instance if the name is given
+#[tauri::command]
+pub fn get_clash_logs(instance: Option<String>) -> CmdResult<VecDeque<String>> {
+    match instance {
+        Some(name) => wrap_err!(CoreManager::global().instance_logs(&name)),
+        None => Ok(logger::Logger::global().get_log()),
+    }
+}
+
+#[tauri::command]
+pub fn get_monitor_stats() -> CmdResult<monitor::MonitorStats> {
+    Ok(monitor::Monitor::global().stats())
+}
+
 #[tauri::command]
-pub fn get_clash_logs() -> CmdResult<VecDeque<String>> {
-    Ok(logger::Logger::global().get_log())
+pub fn get_traffic_stats(query: traffic::TrafficQuery) -> CmdResult<Vec<traffic::TrafficRecord>> {
+    Ok(traffic::TrafficStats::global().query(&query))
+}
+
+#[tauri::command]
+pub fn get_monitor_logs() -> CmdResult<VecDeque<monitor::CoreLog>> {
+    Ok(monitor::Monitor::global().logs())
 }
 
 #[tauri::command]
@@ -324,6 +480,51 @@ pub mod uwp {
     }
 }
 
+#[tauri::command]
+pub async fn get_connections(
+    filter: Option<connections::ConnectionFilter>,
+) -> CmdResult<Vec<clash_api::Connection>> {
+    let filter = filter.unwrap_or_default();
+    wrap_err!(connections::get_connections(&filter).await)
+}
+
+/// close all the connections if the filter is empty
+#[tauri::command]
+pub async fn close_connections(filter: connections::ConnectionFilter) -> CmdResult<usize> {
+    wrap_err!(connections::close_connections(&filter).await)
+}
+
+#[tauri::command]
+pub async fn select_proxy(group: String, proxy: String) -> CmdResult {
+    wrap_err!(connections::select_proxy(&group, &proxy).await)
+}
+
+/// test the latency of the group or all the proxies now, return the count
+#[tauri::command]
+pub async fn test_latency(group: Option<String>) -> CmdResult<usize> {
+    let tester = latency::LatencyTester::global();
+    wrap_err!(tester.test_all(group.as_deref()).await)
+}
+
+/// rank the proxies by the latency history
+#[tauri::command]
+pub async fn get_latency_ranking(group: Option<String>) -> CmdResult<Vec<latency::LatencyStats>> {
+    let tester = latency::LatencyTester::global();
+    wrap_err!(tester.ranking(group.as_deref()).await)
+}
+
+#[tauri::command]
+pub fn get_latency_history(name: String) -> CmdResult<Vec<latency::LatencyRecord>> {
+    Ok(latency::LatencyTester::global().history(&name))
+}
+
+/// re-apply the selections saved in the current profile
+#[tauri::command]
+pub async fn restore_selected() -> CmdResult {
+    let api = wrap_err!(clash_api::ClashApi::current())?;
+    wrap_err!(selected::restore_selected(&api).await)
+}
+
 #[tauri::command]
 pub async fn clash_api_get_proxy_delay(
     name: String,
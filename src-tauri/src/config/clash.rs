@@ -1,3 +1,4 @@
+use super::IClash;
 use crate::utils::{dirs, help};
 use anyhow::Result;
 use nanoid::nanoid;
@@ -118,46 +119,43 @@ impl IClashConfig {
         )
     }
 
+    /// parse the config with the typed schema
+    pub fn typed(&self) -> Result<IClash> {
+        IClash::from_mapping(&self.0)
+    }
+
     pub fn get_mode(&self) -> String {
-        self.0
-            .get("mode")
-            .and_then(|value| match value {
-                Value::String(val_str) => Some(val_str.clone()),
-                _ => None,
-            })
+        self.typed()
+            .ok()
+            .and_then(|clash| clash.mode)
             .unwrap_or("rule".to_string())
     }
 
     pub fn get_enable_tun(&self) -> bool {
-        let config = &self.0;
-        let tun_val = config
-            .get("tun")
-            .and_then(|vale| match vale {
-                Value::Mapping(val_map) => Some(val_map.clone()),
-                _ => None,
-            })
-            .unwrap_or(Mapping::new());
-        *tun_val
-            .get("enable")
-            .and_then(|value| match value {
-                Value::Bool(val_bool) => Some(val_bool),
-                _ => None,
-            })
-            .unwrap_or(&false)
+        self.typed().is_ok_and(|clash| clash.get_enable_tun())
     }
 
     pub fn get_mixed_port(&self) -> u16 {
-        Self::guard_mixed_port(&self.0)
+        self.typed()
+            .ok()
+            .and_then(|clash| clash.get_mixed_port())
+            .unwrap_or(7890)
     }
 
     #[allow(unused)]
     pub fn get_socks_port(&self) -> u16 {
-        Self::guard_socks_port(&self.0)
+        self.typed()
+            .ok()
+            .and_then(|clash| clash.socks_port)
+            .map_or(0, |port| port.0)
     }
 
     #[allow(unused)]
     pub fn get_port(&self) -> u16 {
-        Self::guard_port(&self.0)
+        self.typed()
+            .ok()
+            .and_then(|clash| clash.port)
+            .map_or(0, |port| port.0)
     }
 
     pub fn get_client_info(&self) -> ClashInfo {
@@ -362,52 +360,22 @@ fn test_clash_info() {
     );
 }
 
-#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
-pub struct IClash {
-    pub mixed_port: Option<u16>,
-    pub allow_lan: Option<bool>,
-    pub log_level: Option<String>,
-    pub ipv6: Option<bool>,
-    pub mode: Option<String>,
-    pub external_controller: Option<String>,
-    pub secret: Option<String>,
-    pub dns: Option<IClashDNS>,
-    pub tun: Option<IClashTUN>,
-    pub interface_name: Option<String>,
-}
-
-#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
-pub struct IClashTUN {
-    pub enable: Option<bool>,
-    pub stack: Option<String>,
-    pub auto_route: Option<bool>,
-    pub auto_detect_interface: Option<bool>,
-    pub dns_hijack: Option<Vec<String>>,
-}
-
-#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
-pub struct IClashDNS {
-    pub enable: Option<bool>,
-    pub listen: Option<String>,
-    pub default_nameserver: Option<Vec<String>>,
-    pub enhanced_mode: Option<String>,
-    pub fake_ip_range: Option<String>,
-    pub use_hosts: Option<bool>,
-    pub fake_ip_filter: Option<Vec<String>>,
-    pub nameserver: Option<Vec<String>>,
-    pub fallback: Option<Vec<String>>,
-    pub fallback_filter: Option<IClashFallbackFilter>,
-    pub nameserver_policy: Option<Vec<String>>,
-}
-
-#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
-pub struct IClashFallbackFilter {
-    pub geoip: Option<bool>,
-    pub geoip_code: Option<String>,
-    pub ipcidr: Option<Vec<String>>,
-    pub domain: Option<Vec<String>>,
+#[test]
+fn test_typed_getters() {
+    let config = r#"
+    mixed-port: "7891"
+    mode: global
+    tun:
+      enable: true
+  "#;
+    let clash = IClashConfig(serde_yaml::from_str(config).unwrap());
+    assert_eq!(clash.get_mixed_port(), 7891);
+    assert_eq!(clash.get_mode(), "global");
+    assert!(clash.get_enable_tun());
+
+    // the defaults are used when the config can not be parsed
+    let clash = IClashConfig(serde_yaml::from_str("mixed-port: 70000").unwrap());
+    assert_eq!(clash.get_mixed_port(), 7890);
+    assert_eq!(clash.get_mode(), "rule");
+    assert!(!clash.get_enable_tun());
 }
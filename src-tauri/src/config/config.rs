@@ -1,6 +1,6 @@
-use super::{Draft, IClashConfig, IProfiles, IRuntime, IVerge};
+use super::{Draft, IClash, IClashConfig, IProfiles, IRuntime, IVerge};
 use crate::{
-    core::service,
+    core::{instance, service},
     enhance, feat,
     utils::{dirs, help},
 };
@@ -70,6 +70,7 @@ impl Config {
         let path = match typ {
             ConfigType::Run => dirs::app_home_dir()?.join(RUNTIME_CONFIG),
             ConfigType::Check => temp_dir().join(CHECK_CONFIG),
+            ConfigType::Instance(name) => return Self::generate_instance_file(&name),
         };
 
         let runtime = Config::runtime();
@@ -83,10 +84,25 @@ impl Config {
         Ok(path)
     }
 
+    /// 内核实例用自己的订阅、端口和外部控制
+    fn generate_instance_file(name: &str) -> Result<PathBuf> {
+        let instance = instance::find(name)?;
+        let config = enhance::enhance_profile(&instance.profile)?;
+        let config = instance::use_instance(config, &instance);
+
+        let path = instance::config_path(name)?;
+        help::save_yaml(&path, &config, Some("# Generated by Clash Verge"))?;
+        Ok(path)
+    }
+
     /// 生成订阅存好
     pub fn generate() -> Result<()> {
         let (config, exists_keys, logs) = enhance::enhance();
 
+        for err in IClash::validate(&config) {
+            log::warn!(target: "app", "runtime config: {err}");
+        }
+
         *Config::runtime().draft() = IRuntime {
             config: Some(config),
             exists_keys,
@@ -160,4 +176,6 @@ impl Config {
 pub enum ConfigType {
     Run,
     Check,
+    /// the runtime config of the named core instance
+    Instance(String),
 }
@@ -4,7 +4,9 @@ mod config;
 mod draft;
 mod prfitem;
 mod profiles;
+mod rule;
 mod runtime;
+mod schema;
 mod verge;
 
 pub use self::clash::*;
@@ -12,7 +14,9 @@ pub use self::config::*;
 pub use self::draft::*;
 pub use self::prfitem::*;
 pub use self::profiles::*;
+pub use self::rule::*;
 pub use self::runtime::*;
+pub use self::schema::*;
 pub use self::verge::*;
 
 pub const DEFAULT_PAC: &str = r#"function FindProxyForURL(url, host) {
@@ -2,11 +2,11 @@ use crate::utils::{dirs, help, resolve::VERSION, tmpl};
 use anyhow::{bail, Context, Result};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use serde_yaml::Mapping;
+use serde_yaml::{Mapping, Value};
 use std::{collections::HashMap, fs, path::PathBuf};
 use sysproxy::Sysproxy;
 
-use super::Config;
+use super::{Config, RULE_SECTIONS};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct PrfItem {
@@ -409,4 +409,52 @@ impl PrfItem {
         let path = dirs::app_profiles_dir()?.join(file);
         fs::write(path, data.as_bytes()).context("failed to save the file")
     }
+
+    /// get the rules of the merge profile
+    /// `section` should be one of `RULE_SECTIONS`
+    pub fn get_merge_rules(&self, section: &str) -> Result<Vec<String>> {
+        let merge = self.read_merge()?;
+        Self::merge_rules(&merge, section)
+    }
+
+    /// edit the rules of the merge profile and save it
+    /// the comments in the file would be lost
+    pub fn edit_merge_rules<F>(&self, section: &str, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Vec<String>) -> Result<()>,
+    {
+        let mut merge = self.read_merge()?;
+        let mut rules = Self::merge_rules(&merge, section)?;
+        f(&mut rules)?;
+        merge.insert(section.into(), rules.into());
+        self.save_file(serde_yaml::to_string(&merge)?)
+    }
+
+    fn read_merge(&self) -> Result<Mapping> {
+        if self.itype.as_deref() != Some("merge") {
+            bail!("the profile is not a merge profile");
+        }
+        let data = self.read_file()?;
+        match serde_yaml::from_str::<Option<Mapping>>(&data) {
+            Ok(merge) => Ok(merge.unwrap_or_default()),
+            Err(err) => bail!("failed to parse the merge profile: {err}"),
+        }
+    }
+
+    fn merge_rules(merge: &Mapping, section: &str) -> Result<Vec<String>> {
+        if !RULE_SECTIONS.contains(&section) {
+            bail!("invalid rule section \"{section}\"");
+        }
+        match merge.get(section) {
+            None | Some(Value::Null) => Ok(vec![]),
+            Some(Value::Sequence(rules)) => rules
+                .iter()
+                .map(|rule| match rule.as_str() {
+                    Some(rule) => Ok(rule.to_string()),
+                    None => bail!("the rule should be a string, got {rule:?}"),
+                })
+                .collect(),
+            Some(_) => bail!("\"{section}\" should be a list"),
+        }
+    }
 }
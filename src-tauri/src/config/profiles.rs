@@ -1,6 +1,7 @@
-use super::prfitem::PrfItem;
+use super::prfitem::{PrfItem, PrfOption};
 use crate::utils::{dirs, help};
 use anyhow::{bail, Context, Result};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
 use std::{collections::HashMap, fs, io::Write, path::PathBuf};
@@ -18,6 +19,20 @@ pub struct IProfiles {
     pub items: Option<Vec<PrfItem>>,
 }
 
+/// what to do when the appended remote profile already exists,
+/// compared by the url after `normalize_url`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateMode {
+    /// keep the name and the selected proxies, update the content and merge the option
+    #[default]
+    Merge,
+    /// replace the existing item, only keep the uid, the url and the file
+    Replace,
+    /// append as a new item anyway
+    Append,
+}
+
 macro_rules! patch {
     ($lv: expr, $rv: expr, $key: tt) => {
         if ($rv.$key).is_some() {
@@ -111,38 +126,83 @@ impl IProfiles {
         bail!("failed to get the profile item \"uid:{uid}\"");
     }
 
-    /// append new item
+    /// the remote profile subscribed from the url
+    pub fn get_item_by_url(&self, url: &str) -> Option<&PrfItem> {
+        self.find_items_by_url(url).into_iter().next()
+    }
+
+    /// the remote profiles subscribed from the url, compared after `normalize_url`
+    /// the old versions may have imported the same url more than once
+    pub fn find_items_by_url(&self, url: &str) -> Vec<&PrfItem> {
+        let url = normalize_url(url);
+        let items = self.items.as_deref().unwrap_or_default();
+        items
+            .iter()
+            .filter(|item| is_remote(item))
+            .filter(|item| item.url.as_deref().map(normalize_url).as_ref() == Some(&url))
+            .collect()
+    }
+
+    /// append new item, return the uid of the appended or the updated item
     /// if the file_data is some
     /// then should save the data to file
-    pub fn append_item(&mut self, mut item: PrfItem) -> Result<()> {
-        if item.uid.is_none() {
-            bail!("the uid should not be null");
+    pub fn append_item(&mut self, mut item: PrfItem, mode: DuplicateMode) -> Result<String> {
+        let uid = match item.uid.clone() {
+            Some(uid) => uid,
+            None => bail!("the uid should not be null"),
+        };
+
+        if mode != DuplicateMode::Append {
+            if let Some(index) = self.find_duplicate(&item) {
+                return self.merge_item(index, item, mode);
+            }
         }
 
         // save the file data
         // move the field value after save
         if let Some(file_data) = item.file_data.take() {
-            if item.file.is_none() {
-                bail!("the file should not be null");
+            match item.file.as_ref() {
+                Some(file) => write_profile(file, &file_data)?,
+                None => bail!("the file should not be null"),
             }
+        }
 
-            let file = item.file.clone().unwrap();
-            let path = dirs::app_profiles_dir()?.join(&file);
+        self.items.get_or_insert_with(Vec::new).push(item);
+        self.save_file()?;
+        Ok(uid)
+    }
 
-            fs::File::create(path)
-                .with_context(|| format!("failed to create file \"{}\"", file))?
-                .write(file_data.as_bytes())
-                .with_context(|| format!("failed to write to file \"{}\"", file))?;
+    /// the index of the remote item with the same url
+    fn find_duplicate(&self, item: &PrfItem) -> Option<usize> {
+        if !is_remote(item) {
+            return None;
         }
+        let url = normalize_url(item.url.as_deref()?);
+        self.items.as_ref()?.iter().position(|each| {
+            is_remote(each) && each.url.as_deref().map(normalize_url).as_ref() == Some(&url)
+        })
+    }
 
-        if self.items.is_none() {
-            self.items = Some(vec![]);
+    /// update the existing item at the index with the new one
+    fn merge_item(
+        &mut self,
+        index: usize,
+        mut item: PrfItem,
+        mode: DuplicateMode,
+    ) -> Result<String> {
+        let items = self.items.get_or_insert_with(Vec::new);
+        let each = &mut items[index];
+        let file_data = item.file_data.take();
+        merge_into(each, item, mode);
+
+        let uid = each.uid.clone().unwrap_or_default();
+        if let (Some(file), Some(file_data)) = (each.file.as_ref(), file_data) {
+            write_profile(file, &file_data)?;
         }
+        log::info!(target: "app", "the profile already exists, {mode:?} into \"uid:{uid}\"");
 
-        if let Some(items) = self.items.as_mut() {
-            items.push(item)
-        }
-        self.save_file()
+        self.save_file()?;
+        Ok(uid)
     }
 
     /// reorder items
@@ -298,18 +358,167 @@ impl IProfiles {
 
     /// 获取current指向的订阅内容
     pub fn current_mapping(&self) -> Result<Mapping> {
-        match (self.current.as_ref(), self.items.as_ref()) {
-            (Some(current), Some(items)) => {
-                if let Some(item) = items.iter().find(|e| e.uid.as_ref() == Some(current)) {
-                    let file_path = match item.file.as_ref() {
-                        Some(file) => dirs::app_profiles_dir()?.join(file),
-                        None => bail!("failed to get the file field"),
-                    };
-                    return help::read_merge_mapping(&file_path);
-                }
-                bail!("failed to find the current profile \"uid:{current}\"");
+        match self.current.as_ref() {
+            Some(current) => self.mapping_of(current),
+            None => Ok(Mapping::new()),
+        }
+    }
+
+    /// 读取订阅文件的内容
+    pub fn mapping_of(&self, uid: &str) -> Result<Mapping> {
+        let items = self.items.as_deref().unwrap_or_default();
+        match items.iter().find(|e| e.uid.as_deref() == Some(uid)) {
+            Some(item) => {
+                let file_path = match item.file.as_ref() {
+                    Some(file) => dirs::app_profiles_dir()?.join(file),
+                    None => bail!("failed to get the file field"),
+                };
+                help::read_merge_mapping(&file_path)
             }
-            _ => Ok(Mapping::new()),
+            None => bail!("failed to find the profile \"uid:{uid}\""),
         }
     }
 }
+
+/// the url of the existing item is kept, it's the same after `normalize_url`
+fn merge_into(each: &mut PrfItem, mut item: PrfItem, mode: DuplicateMode) {
+    match mode {
+        DuplicateMode::Replace => {
+            item.uid = each.uid.take();
+            item.url = each.url.take().or(item.url);
+            item.file = each.file.take().or(item.file);
+            *each = item;
+        }
+        _ => {
+            if each.name.is_none() {
+                each.name = item.name;
+            }
+            each.extra = item.extra;
+            each.updated = item.updated;
+            each.home = item.home;
+            each.option = PrfOption::merge(each.option.take(), item.option);
+        }
+    }
+}
+
+fn is_remote(item: &PrfItem) -> bool {
+    item.itype.as_deref() == Some("remote")
+}
+
+fn write_profile(file: &str, data: &str) -> Result<()> {
+    let path = dirs::app_profiles_dir()?.join(file);
+    fs::File::create(path)
+        .with_context(|| format!("failed to create file \"{file}\""))?
+        .write_all(data.as_bytes())
+        .with_context(|| format!("failed to write to file \"{file}\""))
+}
+
+/// the url to compare, the scheme and the host are lowercase,
+/// the default port, the fragment and the trailing slash are dropped,
+/// the query pairs are sorted
+pub fn normalize_url(url: &str) -> String {
+    let url = url.trim();
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    parsed.set_fragment(None);
+
+    let mut pairs = parsed
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect::<Vec<_>>();
+    pairs.sort();
+    if pairs.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+
+    let path = parsed.path().trim_end_matches('/').to_string();
+    parsed.set_path(&path);
+    parsed.to_string()
+}
+
+#[test]
+fn test_duplicate_profile() {
+    assert_eq!(
+        normalize_url(" HTTPS://Example.COM:443/sub/?b=2&a=1#frag "),
+        normalize_url("https://example.com/sub?a=1&b=2")
+    );
+    assert_eq!(
+        normalize_url("https://example.com"),
+        normalize_url("https://example.com/")
+    );
+    assert_ne!(
+        normalize_url("https://example.com/sub?token=a"),
+        normalize_url("https://example.com/sub?token=b")
+    );
+    assert_eq!(normalize_url(" not a url "), "not a url");
+
+    let remote = |uid: &str, url: &str| PrfItem {
+        uid: Some(uid.into()),
+        itype: Some("remote".into()),
+        name: Some(uid.into()),
+        url: Some(url.into()),
+        ..PrfItem::default()
+    };
+    let profiles = IProfiles {
+        items: Some(vec![
+            remote("R1", "https://example.com/sub?token=a"),
+            PrfItem {
+                itype: Some("local".into()),
+                ..remote("L1", "https://example.com/other")
+            },
+        ]),
+        ..IProfiles::default()
+    };
+
+    let item = remote("R2", "https://EXAMPLE.com/sub/?token=a");
+    assert_eq!(profiles.find_duplicate(&item), Some(0));
+    assert_eq!(
+        profiles
+            .get_item_by_url(&item.url.clone().unwrap())
+            .unwrap()
+            .uid,
+        Some("R1".into())
+    );
+    assert_eq!(
+        profiles.find_duplicate(&remote("R3", "https://example.com/other")),
+        None
+    );
+
+    let mut each = profiles.items.clone().unwrap().remove(0);
+    each.file = Some("R1.yaml".into());
+    each.option = Some(PrfOption {
+        user_agent: Some("clash".into()),
+        ..PrfOption::default()
+    });
+    let item = PrfItem {
+        updated: Some(1),
+        file: Some("R2.yaml".into()),
+        option: Some(PrfOption {
+            update_interval: Some(60),
+            ..PrfOption::default()
+        }),
+        ..item
+    };
+
+    let mut merged = each.clone();
+    merge_into(&mut merged, item.clone(), DuplicateMode::Merge);
+    assert_eq!(merged.uid, Some("R1".into()));
+    assert_eq!(merged.name, Some("R1".into()));
+    assert_eq!(merged.file, Some("R1.yaml".into()));
+    assert_eq!(merged.url, Some("https://example.com/sub?token=a".into()));
+    assert_eq!(merged.updated, Some(1));
+    let option = merged.option.unwrap();
+    assert_eq!(option.user_agent, Some("clash".into()));
+    assert_eq!(option.update_interval, Some(60));
+
+    let mut replaced = each.clone();
+    merge_into(&mut replaced, item, DuplicateMode::Replace);
+    assert_eq!(replaced.uid, Some("R1".into()));
+    assert_eq!(replaced.name, Some("R2".into()));
+    assert_eq!(replaced.file, Some("R1.yaml".into()));
+    assert_eq!(replaced.url, Some("https://example.com/sub?token=a".into()));
+    assert_eq!(replaced.option.unwrap().user_agent, None);
+}
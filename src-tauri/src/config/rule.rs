@@ -0,0 +1,370 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// the rule types supported by mihomo
+pub const RULE_TYPES: [&str; 36] = [
+    "DOMAIN",
+    "DOMAIN-SUFFIX",
+    "DOMAIN-KEYWORD",
+    "DOMAIN-REGEX",
+    "GEOSITE",
+    "IP-CIDR",
+    "IP-CIDR6",
+    "IP-SUFFIX",
+    "IP-ASN",
+    "GEOIP",
+    "SRC-GEOIP",
+    "SRC-IP-ASN",
+    "SRC-IP-CIDR",
+    "SRC-IP-SUFFIX",
+    "DST-PORT",
+    "SRC-PORT",
+    "IN-PORT",
+    "IN-TYPE",
+    "IN-USER",
+    "IN-NAME",
+    "PROCESS-PATH",
+    "PROCESS-PATH-REGEX",
+    "PROCESS-NAME",
+    "PROCESS-NAME-REGEX",
+    "UID",
+    "NETWORK",
+    "DSCP",
+    "RULE-SET",
+    "AND",
+    "OR",
+    "NOT",
+    "SUB-RULE",
+    "MATCH",
+    // the old names still accepted by mihomo
+    "GEOIP6",
+    "SCRIPT",
+    "PROCESS",
+];
+
+/// the rule types which could resolve the domain to ip
+const IP_RULE_TYPES: [&str; 7] = [
+    "IP-CIDR",
+    "IP-CIDR6",
+    "IP-SUFFIX",
+    "IP-ASN",
+    "GEOIP",
+    "GEOIP6",
+    "RULE-SET",
+];
+
+/// 可以放进 merge 的 rules 字段
+pub const RULE_SECTIONS: [&str; 3] = ["rules", "prepend-rules", "append-rules"];
+
+/// ### structured mihomo rule
+///
+/// - `DOMAIN-SUFFIX,google.com,PROXY`
+/// - `IP-CIDR,10.0.0.0/8,DIRECT,no-resolve`
+/// - `AND,((DOMAIN,baidu.com),(NETWORK,UDP)),DIRECT`
+/// - `SUB-RULE,(NETWORK,TCP),sub-rule-name`
+/// - `MATCH,PROXY`
+///
+/// the rules inside the logic rules have no target
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IRule {
+    /// uppercase rule type
+    pub rtype: String,
+
+    /// payload of the normal rules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+
+    /// the sub rules of `AND` / `OR` / `NOT`, the condition of `SUB-RULE`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Vec<IRule>>,
+
+    /// proxy name, or the sub rule name of `SUB-RULE`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+
+    /// like `no-resolve`, `src`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+}
+
+impl IRule {
+    /// parse and check the rule in the `rules` list
+    pub fn parse(rule: &str) -> Result<Self> {
+        let rule = Self::parse_inner(rule, true)?;
+        rule.validate(true)?;
+        Ok(rule)
+    }
+
+    fn parse_inner(rule: &str, with_target: bool) -> Result<Self> {
+        let parts = split_rule(rule)?;
+        let mut parts = parts.into_iter();
+
+        let rtype = match parts.next() {
+            Some(rtype) if !rtype.is_empty() => rtype.to_uppercase(),
+            _ => bail!("empty rule"),
+        };
+        let mut item = IRule {
+            rtype,
+            ..Self::default()
+        };
+
+        match item.rtype.as_str() {
+            "MATCH" => {}
+            "AND" | "OR" | "NOT" => {
+                let payload = parts.next().unwrap_or_default();
+                let rules = split_top(unwrap_paren(&payload)?)?
+                    .iter()
+                    .map(|sub| Self::parse_inner(unwrap_paren(sub)?, false))
+                    .collect::<Result<Vec<_>>>()?;
+                item.rules = Some(rules);
+            }
+            "SUB-RULE" => {
+                let payload = parts.next().unwrap_or_default();
+                item.rules = Some(vec![Self::parse_inner(unwrap_paren(&payload)?, false)?]);
+            }
+            _ => item.payload = parts.next(),
+        }
+
+        if with_target {
+            item.target = parts.next();
+        }
+        item.options = parts.collect();
+        Ok(item)
+    }
+
+    fn validate(&self, with_target: bool) -> Result<()> {
+        let rtype = self.rtype.as_str();
+        if !RULE_TYPES.contains(&rtype) {
+            bail!("unknown rule type `{rtype}`");
+        }
+
+        match rtype {
+            "MATCH" if !with_target => bail!("`MATCH` could not be used in the logic rules"),
+            "MATCH" => {}
+            "AND" | "OR" | "NOT" | "SUB-RULE" => {
+                let rules = self.rules.as_deref().unwrap_or_default();
+                if rules.is_empty() {
+                    bail!("`{rtype}` rule requires at least one sub rule");
+                }
+                if rtype == "NOT" && rules.len() != 1 {
+                    bail!("`NOT` rule requires exactly one sub rule");
+                }
+                for rule in rules {
+                    rule.validate(false)?;
+                }
+            }
+            _ => {
+                if self.payload.as_deref().unwrap_or_default().is_empty() {
+                    bail!("`{rtype}` rule requires a payload");
+                }
+            }
+        }
+
+        if with_target && self.target.as_deref().unwrap_or_default().is_empty() {
+            bail!("`{rtype}` rule requires a target");
+        }
+
+        for option in self.options.iter() {
+            match option.as_str() {
+                "no-resolve" if IP_RULE_TYPES.contains(&rtype) => {}
+                "src" if IP_RULE_TYPES.contains(&rtype) && rtype != "RULE-SET" => {}
+                "no-resolve" | "src" => bail!("`{option}` is not supported by `{rtype}` rule"),
+                _ => bail!("unknown rule option `{option}`"),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for IRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for IRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.rtype)?;
+
+        if let Some(payload) = &self.payload {
+            write!(f, ",{payload}")?;
+        }
+        if let Some(rules) = &self.rules {
+            let rules = rules.iter().map(|r| format!("({r})"));
+            match self.rtype.as_str() {
+                "SUB-RULE" => write!(f, ",{}", rules.collect::<String>())?,
+                _ => write!(f, ",({})", rules.collect::<Vec<_>>().join(","))?,
+            }
+        }
+        if let Some(target) = &self.target {
+            write!(f, ",{target}")?;
+        }
+        for option in self.options.iter() {
+            write!(f, ",{option}")?;
+        }
+        Ok(())
+    }
+}
+
+/// the rule in the merge file, keep the raw text if it could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IRuleEntry {
+    pub raw: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<IRule>,
+
+    /// the reason why the rule could not be parsed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl IRuleEntry {
+    pub fn parse(raw: &str) -> Self {
+        let (rule, error) = match IRule::parse(raw) {
+            Ok(rule) => (Some(rule), None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        Self {
+            raw: raw.to_string(),
+            rule,
+            error,
+        }
+    }
+}
+
+/// only the payload of the logic rules is wrapped in parentheses,
+/// the other payloads like `DOMAIN-REGEX` may contain the parentheses
+fn split_rule(rule: &str) -> Result<Vec<String>> {
+    let rtype = rule.trim().split(',').next().unwrap_or_default();
+    match rtype.trim().to_uppercase().as_str() {
+        "AND" | "OR" | "NOT" | "SUB-RULE" => split_top(rule),
+        _ => Ok(rule
+            .trim()
+            .split(',')
+            .map(|p| p.trim().to_string())
+            .collect()),
+    }
+}
+
+/// split by the commas outside the parentheses
+fn split_top(s: &str) -> Result<Vec<String>> {
+    let mut parts = vec![];
+    let mut depth = 0usize;
+    let mut current = String::new();
+
+    for c in s.trim().chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => bail!("unbalanced parentheses in `{s}`"),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+
+    if depth != 0 {
+        bail!("unbalanced parentheses in `{s}`");
+    }
+    parts.push(current.trim().to_string());
+    Ok(parts)
+}
+
+/// `(xxx)` -> `xxx`
+fn unwrap_paren(s: &str) -> Result<&str> {
+    let s = s.trim();
+    match s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        Some(inner) => Ok(inner),
+        None => bail!("expected the rule wrapped in parentheses, got `{s}`"),
+    }
+}
+
+#[test]
+fn test_parse_rule() {
+    let rule = IRule::parse("IP-CIDR,10.0.0.0/8,DIRECT,no-resolve").unwrap();
+    assert_eq!(rule.rtype, "IP-CIDR");
+    assert_eq!(rule.payload.as_deref(), Some("10.0.0.0/8"));
+    assert_eq!(rule.target.as_deref(), Some("DIRECT"));
+    assert_eq!(rule.options, vec!["no-resolve"]);
+
+    let rule = IRule::parse("AND,((DOMAIN,baidu.com),(NOT,((NETWORK,UDP)))),DIRECT").unwrap();
+    let rules = rule.rules.as_ref().unwrap();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].payload.as_deref(), Some("baidu.com"));
+    assert_eq!(rules[0].target, None);
+    assert_eq!(rules[1].rtype, "NOT");
+    assert_eq!(rule.target.as_deref(), Some("DIRECT"));
+
+    let rule = IRule::parse("SUB-RULE,(NETWORK,TCP),sub-rule").unwrap();
+    assert_eq!(rule.rules.as_ref().unwrap()[0].rtype, "NETWORK");
+    assert_eq!(rule.target.as_deref(), Some("sub-rule"));
+
+    let rule = IRule::parse(r"DOMAIN-REGEX,^a\(b$,DIRECT").unwrap();
+    assert_eq!(rule.payload.as_deref(), Some(r"^a\(b$"));
+    assert_eq!(rule.target.as_deref(), Some("DIRECT"));
+
+    let rule = IRule::parse("match,PROXY").unwrap();
+    assert_eq!(rule.rtype, "MATCH");
+    assert_eq!(rule.payload, None);
+
+    // serialize back
+    for rule in [
+        "DOMAIN-SUFFIX,google.com,PROXY",
+        "GEOIP,CN,DIRECT,no-resolve",
+        "RULE-SET,reject,REJECT",
+        "AND,((DOMAIN,baidu.com),(NOT,((NETWORK,UDP)))),DIRECT",
+        "SUB-RULE,(OR,((NETWORK,TCP),(DST-PORT,443))),sub-rule",
+        "MATCH,PROXY",
+        "PROCESS-NAME,app (x86).exe,DIRECT",
+    ] {
+        assert_eq!(IRule::parse(rule).unwrap().to_string(), rule);
+    }
+
+    for rule in [
+        "",
+        "UNKNOWN,abc,PROXY",
+        "DOMAIN,google.com",
+        "DOMAIN,,PROXY",
+        "DOMAIN,google.com,PROXY,no-resolve",
+        "IP-CIDR,10.0.0.0/8,DIRECT,resolve",
+        "NOT,((DOMAIN,a.com),(DOMAIN,b.com)),PROXY",
+        "AND,(DOMAIN,a.com),PROXY",
+        "AND,((DOMAIN,a.com),(MATCH)),PROXY",
+        "OR,((DOMAIN,a.com),PROXY",
+    ] {
+        assert!(IRule::parse(rule).is_err(), "{rule}");
+    }
+}
+
+#[test]
+fn test_rule_entries() {
+    let entries = [
+        "DOMAIN,a.com,PROXY",
+        "DOMAIN-WILDCARD,*.a.com,PROXY",
+        "MATCH,DIRECT",
+    ]
+    .map(IRuleEntry::parse);
+
+    assert_eq!(
+        entries[0].rule,
+        Some(IRule::parse("DOMAIN,a.com,PROXY").unwrap())
+    );
+    assert_eq!(entries[0].error, None);
+
+    // the bad rule keeps its raw text, the others are still parsed
+    assert_eq!(entries[1].raw, "DOMAIN-WILDCARD,*.a.com,PROXY");
+    assert_eq!(entries[1].rule, None);
+    assert_eq!(
+        entries[1].error.as_deref(),
+        Some("unknown rule type `DOMAIN-WILDCARD`")
+    );
+    assert!(entries[2].rule.is_some());
+}
@@ -0,0 +1,1096 @@
+//! typed model of the mihomo configuration
+//!
+//! every struct keeps the fields it does not know in `extra`,
+//! so a config can be parsed and serialized again without losing anything.
+//! the sections that fail to parse are kept as the raw value, see [`Typed`].
+//! [`IClash::to_mapping`] keeps the key order and the scalar form of the parsed config.
+
+use super::IRule;
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashSet;
+
+/// the proxy names which are always available
+pub const BUILTIN_PROXIES: [&str; 5] = ["DIRECT", "REJECT", "REJECT-DROP", "PASS", "COMPATIBLE"];
+
+/// 能解析的部分使用类型，否则保留原始的值
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Typed<T> {
+    Known(T),
+    Raw(Value),
+}
+
+impl<T> Typed<T> {
+    pub fn known(&self) -> Option<&T> {
+        match self {
+            Typed::Known(val) => Some(val),
+            Typed::Raw(_) => None,
+        }
+    }
+}
+
+/// port number, allow the string value like `"7890"`
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Port(pub u16);
+
+impl<'de> Deserialize<'de> for Port {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let port = match Value::deserialize(deserializer)? {
+            Value::Number(num) => num.as_u64().and_then(|n| u16::try_from(n).ok()),
+            Value::String(s) => s.trim().parse::<u16>().ok(),
+            _ => None,
+        };
+        port.map(Port).ok_or(D::Error::custom(
+            "invalid port, expected an integer in 0..=65535",
+        ))
+    }
+}
+
+/// ### mihomo config schema
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IClash {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<Port>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub socks_port: Option<Port>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redir_port: Option<Port>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tproxy_port: Option<Port>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mixed_port: Option<Port>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_lan: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind_address: Option<String>,
+    /// rule | global | direct
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// silent | error | warning | info | debug
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unified_delay: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp_concurrent: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_controller: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_ui: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing_mark: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub find_process_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geodata_mode: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hosts: Option<Mapping>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<Mapping>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Typed<IClashDNS>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tun: Option<Typed<IClashTUN>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sniffer: Option<Typed<IClashSniffer>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listeners: Option<Vec<Typed<IClashListener>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxies: Option<Vec<Typed<IClashProxy>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_providers: Option<IndexMap<String, Typed<IClashProxyProvider>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_groups: Option<Vec<Typed<IClashProxyGroup>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_providers: Option<IndexMap<String, Typed<IClashRuleProvider>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub_rules: Option<IndexMap<String, Vec<String>>>,
+
+    /// the fields not in the model
+    #[serde(flatten)]
+    pub extra: Mapping,
+
+    /// the parsed config, to keep the key order and the scalar form
+    #[serde(skip)]
+    source: Option<Mapping>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IClashTUN {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable: Option<bool>,
+    /// system | gvisor | mixed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_route: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_redirect: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_detect_interface: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict_route: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_hijack: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gso: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_independent_nat: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route_address: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route_exclude_address: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_interface: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_interface: Option<Vec<String>>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IClashDNS {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefer_h3: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub respect_rules: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_hosts: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_system_hosts: Option<bool>,
+    /// fake-ip | redir-host
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enhanced_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fake_ip_range: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fake_ip_filter: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fake_ip_filter_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_nameserver: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nameserver: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_server_nameserver: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direct_nameserver: Option<Vec<String>>,
+    /// the value is a nameserver or a list of nameservers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nameserver_policy: Option<IndexMap<String, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_filter: Option<IClashFallbackFilter>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IClashFallbackFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geoip: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geoip_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geosite: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipcidr: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<Vec<String>>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IClashSniffer {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_dns_mapping: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_pure_ip: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub override_destination: Option<bool>,
+    /// protocol -> options, e.g. `HTTP: { ports: [80, 8080-8880] }`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sniff: Option<IndexMap<String, IClashSniff>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force_domain: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_domain: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_src_address: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_dst_address: Option<Vec<String>>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IClashSniff {
+    /// port number or port range like `8000-9000`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ports: Option<Vec<Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub override_destination: Option<bool>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IClashListener {
+    pub name: String,
+    /// http | socks | mixed | redir | tproxy | tun | shadowsocks | vmess | tuic ...
+    #[serde(rename = "type")]
+    pub ltype: String,
+    pub port: Port,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+/// proxy node, tagged by the `type` field
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum IClashProxy {
+    Direct(IProxyDirect),
+    Http(IProxyHttp),
+    Socks5(IProxySocks5),
+    Ss(IProxyShadowsocks),
+    Ssr(IProxyShadowsocksR),
+    Snell(IProxySnell),
+    Vmess(IProxyVmess),
+    Vless(IProxyVless),
+    Trojan(IProxyTrojan),
+    Hysteria(IProxyHysteria),
+    Hysteria2(IProxyHysteria2),
+    Tuic(IProxyTuic),
+    Wireguard(IProxyWireguard),
+    Ssh(IProxySsh),
+}
+
+impl IClashProxy {
+    pub fn name(&self) -> &str {
+        match self {
+            IClashProxy::Direct(p) => &p.name,
+            IClashProxy::Http(p) => &p.name,
+            IClashProxy::Socks5(p) => &p.name,
+            IClashProxy::Ss(p) => &p.name,
+            IClashProxy::Ssr(p) => &p.name,
+            IClashProxy::Snell(p) => &p.name,
+            IClashProxy::Vmess(p) => &p.name,
+            IClashProxy::Vless(p) => &p.name,
+            IClashProxy::Trojan(p) => &p.name,
+            IClashProxy::Hysteria(p) => &p.name,
+            IClashProxy::Hysteria2(p) => &p.name,
+            IClashProxy::Tuic(p) => &p.name,
+            IClashProxy::Wireguard(p) => &p.name,
+            IClashProxy::Ssh(p) => &p.name,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyDirect {
+    pub name: String,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyHttp {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_cert_verify: Option<bool>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxySocks5 {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyShadowsocks {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    pub cipher: String,
+    pub password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin_opts: Option<Mapping>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyShadowsocksR {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    pub cipher: String,
+    pub password: String,
+    pub obfs: String,
+    pub protocol: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs_param: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol_param: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxySnell {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    pub psk: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs_opts: Option<Mapping>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyVmess {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    pub uuid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alter_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_cert_verify: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servername: Option<String>,
+    /// tcp | ws | http | h2 | grpc
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyVless {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    pub uuid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flow: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub servername: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_fingerprint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reality_opts: Option<Mapping>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyTrojan {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    pub password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_cert_verify: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyHysteria {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ports: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_str: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub up: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs: Option<String>,
+    /// must be a list since meta 1.13.2
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_cert_verify: Option<bool>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyHysteria2 {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ports: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub up: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub obfs_password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_cert_verify: Option<bool>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyTuic {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    /// tuic v4
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// tuic v5
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat_interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub congestion_controller: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp_relay_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reduce_rtt: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxyWireguard {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<Port>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<String>,
+    pub private_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_shared_key: Option<String>,
+    /// a list of numbers or a base64 string
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserved: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub udp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peers: Option<Vec<Mapping>>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IProxySsh {
+    pub name: String,
+    pub server: String,
+    pub port: Port,
+    pub username: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key_passphrase: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_key: Option<Vec<String>>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IClashGroupType {
+    Select,
+    UrlTest,
+    Fallback,
+    LoadBalance,
+    Relay,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IClashProxyGroup {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub gtype: IClashGroupType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxies: Option<Vec<String>>,
+    /// proxy providers
+    #[serde(rename = "use", skip_serializing_if = "Option::is_none")]
+    pub use_providers: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lazy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_failed_times: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_udp: Option<bool>,
+    /// consistent-hashing | round-robin | sticky-sessions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_all: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_all_proxies: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_all_providers: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_filter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_status: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hidden: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IClashProxyProvider {
+    /// http | file | inline
+    #[serde(rename = "type")]
+    pub ptype: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_filter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<Mapping>,
+    #[serde(rename = "override", skip_serializing_if = "Option::is_none")]
+    pub override_opts: Option<Mapping>,
+    /// proxies of the `inline` provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Vec<Typed<IClashProxy>>>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IClashRuleProvider {
+    /// http | file | inline
+    #[serde(rename = "type")]
+    pub ptype: String,
+    /// domain | ipcidr | classical
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub behavior: Option<String>,
+    /// yaml | text | mrs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// rules of the `inline` provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Vec<String>>,
+
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+impl IClash {
+    pub fn from_mapping(config: &Mapping) -> Result<Self> {
+        let mut clash: Self = serde_yaml::from_value(Value::from(config.clone()))
+            .context("failed to parse the config with the mihomo schema")?;
+        clash.source = Some(config.clone());
+        Ok(clash)
+    }
+
+    /// the unchanged parts are the same as the parsed config
+    pub fn to_mapping(&self) -> Result<Mapping> {
+        let mut value = serde_yaml::to_value(self)?;
+        if let Some(source) = self.source.as_ref() {
+            value = restore_form(value, &Value::from(source.clone()));
+        }
+        value
+            .as_mapping()
+            .cloned()
+            .ok_or(anyhow::anyhow!("the config should be a mapping"))
+    }
+
+    pub fn get_mixed_port(&self) -> Option<u16> {
+        self.mixed_port.map(|p| p.0)
+    }
+
+    pub fn get_enable_tun(&self) -> bool {
+        match &self.tun {
+            Some(Typed::Known(tun)) => tun.enable.unwrap_or(false),
+            Some(Typed::Raw(tun)) => tun.get("enable").and_then(Value::as_bool).unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// the names of all proxies and proxy groups, including the raw ones
+    pub fn proxy_names(&self) -> Vec<String> {
+        let proxies = self.proxies.iter().flatten().filter_map(|p| match p {
+            Typed::Known(p) => Some(p.name().to_string()),
+            Typed::Raw(p) => raw_name(p),
+        });
+        let groups = self.proxy_groups.iter().flatten().filter_map(|g| match g {
+            Typed::Known(g) => Some(g.name.clone()),
+            Typed::Raw(g) => raw_name(g),
+        });
+        proxies.chain(groups).collect()
+    }
+
+    /// 检查配置，返回发现的问题
+    pub fn validate(config: &Mapping) -> Vec<String> {
+        let clash = match Self::from_mapping(config) {
+            Ok(clash) => clash,
+            Err(err) => return vec![format!("{err:#}")],
+        };
+
+        let mut errors = vec![];
+
+        check_raw(&mut errors, "dns", clash.dns.as_ref());
+        check_raw(&mut errors, "tun", clash.tun.as_ref());
+        check_raw(&mut errors, "sniffer", clash.sniffer.as_ref());
+        for (i, listener) in clash.listeners.iter().flatten().enumerate() {
+            check_raw(&mut errors, &format!("listeners[{i}]"), Some(listener));
+        }
+        for (i, proxy) in clash.proxies.iter().flatten().enumerate() {
+            check_raw(&mut errors, &format!("proxies[{i}]"), Some(proxy));
+        }
+        for (name, provider) in clash.proxy_providers.iter().flatten() {
+            check_raw(
+                &mut errors,
+                &format!("proxy-providers.{name}"),
+                Some(provider),
+            );
+        }
+        for (i, group) in clash.proxy_groups.iter().flatten().enumerate() {
+            check_raw(&mut errors, &format!("proxy-groups[{i}]"), Some(group));
+        }
+        for (name, provider) in clash.rule_providers.iter().flatten() {
+            check_raw(
+                &mut errors,
+                &format!("rule-providers.{name}"),
+                Some(provider),
+            );
+        }
+
+        // the name of the proxies and the groups should be unique
+        let names = clash.proxy_names();
+        let mut exists = HashSet::new();
+        for name in names.iter() {
+            if !exists.insert(name.as_str()) {
+                errors.push(format!("duplicate proxy name `{name}`"));
+            }
+        }
+
+        // the group should only refer to the exists proxies
+        exists.extend(BUILTIN_PROXIES);
+        let providers = clash.proxy_providers.as_ref();
+        for group in clash
+            .proxy_groups
+            .iter()
+            .flatten()
+            .filter_map(|g| g.known())
+        {
+            for proxy in group.proxies.iter().flatten() {
+                if !exists.contains(proxy.as_str()) {
+                    errors.push(format!(
+                        "proxy group `{}` refers to the unknown proxy `{proxy}`",
+                        group.name
+                    ));
+                }
+            }
+            for provider in group.use_providers.iter().flatten() {
+                if !providers.is_some_and(|p| p.contains_key(provider)) {
+                    errors.push(format!(
+                        "proxy group `{}` uses the unknown provider `{provider}`",
+                        group.name
+                    ));
+                }
+            }
+        }
+
+        // the rules should be valid and target to the exists proxies
+        let sub_rules = clash.sub_rules.as_ref();
+        let rules = clash.rules.iter().flatten().enumerate();
+        let rules = rules.map(|(i, rule)| (format!("rules[{i}]"), rule));
+        let subs = sub_rules.into_iter().flatten().flat_map(|(name, rules)| {
+            let rules = rules.iter().enumerate();
+            rules.map(move |(i, rule)| (format!("sub-rules.{name}[{i}]"), rule))
+        });
+        for (path, rule) in rules.chain(subs) {
+            let rule = match IRule::parse(rule) {
+                Ok(rule) => rule,
+                Err(err) => {
+                    errors.push(format!("{path}: {err}"));
+                    continue;
+                }
+            };
+            let target = rule.target.unwrap_or_default();
+            let found = match rule.rtype.as_str() {
+                "SUB-RULE" => sub_rules.is_some_and(|s| s.contains_key(&target)),
+                _ => exists.contains(target.as_str()),
+            };
+            if !found {
+                errors.push(format!("{path}: unknown target `{target}`"));
+            }
+        }
+
+        errors
+    }
+}
+
+/// put the keys in the order of the source and use the source scalar if it's the same value,
+/// like the port `"7890"` which is serialized as a number
+fn restore_form(value: Value, source: &Value) -> Value {
+    match (value, source) {
+        (Value::Mapping(mut map), Value::Mapping(source)) => {
+            let mut ret = Mapping::new();
+            for (key, source) in source.iter() {
+                if let Some(value) = map.remove(key) {
+                    ret.insert(key.clone(), restore_form(value, source));
+                }
+            }
+            ret.extend(map);
+            Value::Mapping(ret)
+        }
+        (Value::Sequence(seq), Value::Sequence(source)) => {
+            let mut source = source.iter();
+            let seq = seq.into_iter().map(|value| match source.next() {
+                Some(source) => restore_form(value, source),
+                None => value,
+            });
+            Value::Sequence(seq.collect())
+        }
+        (Value::Number(num), Value::String(text))
+            if text.trim().parse::<u64>().ok() == num.as_u64() =>
+        {
+            source.clone()
+        }
+        (value, _) => value,
+    }
+}
+
+fn raw_name(value: &Value) -> Option<String> {
+    value.get("name").and_then(Value::as_str).map(String::from)
+}
+
+/// parse the raw value again to get the reason
+fn check_raw<T: DeserializeOwned>(errors: &mut Vec<String>, path: &str, item: Option<&Typed<T>>) {
+    if let Some(Typed::Raw(value)) = item {
+        if let Err(err) = serde_yaml::from_value::<T>(value.clone()) {
+            errors.push(format!("{path}: {err}"));
+        }
+    }
+}
+
+#[test]
+fn test_schema_roundtrip() {
+    let config = r#"
+    mixed-port: "7890"
+    mode: rule
+    unknown-key: 1
+    tun:
+      enable: true
+      stack: gvisor
+      x-custom: abc
+    dns:
+      enable: true
+      nameserver-policy:
+        "geosite:cn": [223.5.5.5]
+    proxies:
+      - name: ss
+        type: ss
+        server: 1.1.1.1
+        port: 443
+        cipher: aes-128-gcm
+        password: pwd
+        ws-opts:
+          path: /
+      - name: new
+        type: future-protocol
+        server: 2.2.2.2
+    proxy-providers:
+      provider:
+        type: http
+        url: https://example.com
+    proxy-groups:
+      - name: select
+        type: select
+        proxies: [ss, new, DIRECT]
+        use: [provider]
+    rules:
+      - MATCH,select
+  "#;
+
+    let mapping = serde_yaml::from_str::<Mapping>(config).unwrap();
+    let clash = IClash::from_mapping(&mapping).unwrap();
+
+    assert_eq!(clash.get_mixed_port(), Some(7890));
+    assert!(clash.get_enable_tun());
+    assert_eq!(clash.extra.get("unknown-key"), Some(&Value::from(1)));
+    assert!(matches!(
+        clash.proxies.as_ref().unwrap()[0],
+        Typed::Known(_)
+    ));
+    assert!(matches!(clash.proxies.as_ref().unwrap()[1], Typed::Raw(_)));
+
+    // the mapping is the same as the input, including the key order and the scalar form
+    let output = clash.to_mapping().unwrap();
+    assert_eq!(output, mapping);
+    assert_eq!(
+        serde_yaml::to_string(&output).unwrap(),
+        serde_yaml::to_string(&mapping).unwrap()
+    );
+
+    // the typed fields are the known keys
+    let typed = IClash {
+        extra: Mapping::new(),
+        ..clash.clone()
+    };
+    let typed = serde_yaml::to_value(typed).unwrap();
+    let typed = typed.as_mapping().unwrap();
+    assert!(typed.keys().all(|key| key
+        .as_str()
+        .is_some_and(|k| crate::enhance::field::MIHOMO_FIELDS.contains(&k))));
+
+    // the changed value is kept
+    let mut changed = clash.clone();
+    changed.mixed_port = Some(Port(7891));
+    let output = changed.to_mapping().unwrap();
+    assert_eq!(output.get("mixed-port"), Some(&Value::from(7891)));
+
+    let reparsed = IClash::from_mapping(&clash.to_mapping().unwrap()).unwrap();
+    assert_eq!(clash, reparsed);
+
+    let tun = reparsed.to_mapping().unwrap();
+    let tun = tun.get("tun").unwrap();
+    assert_eq!(tun.get("x-custom"), Some(&Value::from("abc")));
+
+    let errors = IClash::validate(&mapping);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("proxies[1]"));
+}
+
+#[test]
+fn test_schema_validate() {
+    let config = r#"
+    proxies:
+      - name: ss
+        type: ss
+        server: 1.1.1.1
+        port: 443
+        cipher: aes-128-gcm
+      - name: ss
+        type: direct
+    proxy-groups:
+      - name: auto
+        type: url-test
+        proxies: [ss, missing]
+        use: [provider]
+      - name: bad
+        type: unknown-group
+    rules:
+      - DOMAIN,a.com,auto
+      - SUB-RULE,(NETWORK,TCP),missing-sub
+      - DOMAIN,b.com
+      - MATCH,bad
+  "#;
+
+    let mapping = serde_yaml::from_str::<Mapping>(config).unwrap();
+    let errors = IClash::validate(&mapping);
+
+    assert!(errors.iter().any(|e| e.starts_with("proxies[0]")));
+    assert!(errors.iter().any(|e| e.starts_with("proxy-groups[1]")));
+    assert!(errors.contains(&"proxy group `auto` refers to the unknown proxy `missing`".into()));
+    assert!(errors.contains(&"proxy group `auto` uses the unknown provider `provider`".into()));
+    assert!(errors.contains(&"duplicate proxy name `ss`".into()));
+    assert!(errors.contains(&"rules[1]: unknown target `missing-sub`".into()));
+    assert!(errors.contains(&"rules[2]: `DOMAIN` rule requires a target".into()));
+    assert_eq!(errors.iter().filter(|e| e.starts_with("rules")).count(), 2);
+}
@@ -10,6 +10,10 @@ pub struct IVerge {
     /// app listening port for app singleton
     pub app_singleton_port: Option<u16>,
 
+    /// the token of the control api on the singleton port,
+    /// generated on the first launch, only edited in the file
+    pub control_token: Option<String>,
+
     /// app log level
     /// silent | error | warn | info | debug | trace
     pub app_log_level: Option<String>,
@@ -107,6 +111,19 @@ pub struct IVerge {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub clash_core: Option<String>,
 
+    /// 用户添加的内核
+    pub custom_cores: Option<Vec<IVergeCore>>,
+
+    /// 额外的内核实例，和主内核同时运行
+    pub core_instances: Option<Vec<IVergeInstance>>,
+
+    /// 内核更新包的下载地址，`.gz` `.zip` 或者可执行文件
+    /// 校验值从 `{url}.sha256` 获取
+    pub core_update_url: Option<String>,
+
+    /// minisign 公钥，设置后会校验 `{url}.minisig` 签名
+    pub core_update_pubkey: Option<String>,
+
     /// hotkey map
     /// format: {func},{key}
     pub hotkeys: Option<Vec<String>>,
@@ -123,9 +140,33 @@ pub struct IVerge {
     /// 默认的延迟测试超时时间
     pub default_latency_timeout: Option<i32>,
 
+    /// 定时批量测试延迟的间隔，单位分钟，0 为关闭
+    pub latency_test_interval: Option<u64>,
+
+    /// 定时测试的分组，为空则测试所有节点
+    pub latency_test_group: Option<String>,
+
+    /// 开启自动故障转移的 selector 分组
+    pub failover_groups: Option<Vec<String>>,
+
+    /// 故障转移检测的间隔，单位秒
+    pub failover_check_interval: Option<u64>,
+
+    /// 当前节点连续失败多少次后切换
+    pub failover_max_failures: Option<u32>,
+
+    /// 两次切换之间的最短间隔，单位秒
+    pub failover_cooldown: Option<u64>,
+
     /// 是否使用内部的脚本支持，默认为真
     pub enable_builtin_enhanced: Option<bool>,
 
+    /// 保持订阅原有的键顺序，不对生成的配置排序，默认为假
+    pub enable_keep_key_order: Option<bool>,
+
+    /// 严格模式，只规范化 mihomo 已知的键，其余的键保持原有大小写，默认为假
+    pub enable_strict_key_case: Option<bool>,
+
     /// proxy 页面布局 列数
     pub proxy_layout_column: Option<i32>,
 
@@ -166,6 +207,31 @@ pub struct IVergeTestItem {
     pub url: Option<String>,
 }
 
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IVergeInstance {
+    /// letters, digits, `-` and `_`, used in the file names
+    pub name: String,
+    /// the uid of the profile
+    pub profile: String,
+    pub mixed_port: u16,
+    /// like `127.0.0.1:9098`
+    pub external_controller: String,
+    pub secret: Option<String>,
+    pub allow_lan: Option<bool>,
+    /// start with the app
+    pub autostart: Option<bool>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IVergeCore {
+    /// used as `clash_core`, must not be the name of the builtin cores
+    pub name: String,
+    /// absolute path of the binary
+    pub path: String,
+    /// the version detected when it's added
+    pub version: Option<String>,
+}
+
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct IVergeTheme {
     pub primary_color: Option<String>,
@@ -225,7 +291,14 @@ impl IVerge {
             proxy_guard_duration: Some(30),
             auto_close_connection: Some(true),
             auto_check_update: Some(true),
+            latency_test_interval: Some(0),
+            failover_groups: Some(vec![]),
+            failover_check_interval: Some(60),
+            failover_max_failures: Some(3),
+            failover_cooldown: Some(300),
             enable_builtin_enhanced: Some(true),
+            enable_keep_key_order: Some(false),
+            enable_strict_key_case: Some(false),
             auto_log_clean: Some(3),
             enable_tray: Some(true),
             ..Self::default()
@@ -283,13 +356,25 @@ impl IVerge {
         patch!(dark_theme_setting);
         patch!(web_ui_list);
         patch!(clash_core);
+        patch!(custom_cores);
+        patch!(core_instances);
+        patch!(core_update_url);
+        patch!(core_update_pubkey);
         patch!(hotkeys);
 
         patch!(auto_close_connection);
         patch!(auto_check_update);
         patch!(default_latency_test);
         patch!(default_latency_timeout);
+        patch!(latency_test_interval);
+        patch!(latency_test_group);
+        patch!(failover_groups);
+        patch!(failover_check_interval);
+        patch!(failover_max_failures);
+        patch!(failover_cooldown);
         patch!(enable_builtin_enhanced);
+        patch!(enable_keep_key_order);
+        patch!(enable_strict_key_case);
         patch!(proxy_layout_column);
         patch!(test_list);
         patch!(auto_log_clean);
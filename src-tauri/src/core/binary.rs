@@ -0,0 +1,263 @@
+use crate::{config::Config, utils::dirs};
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{collections::HashMap, path::PathBuf, time::SystemTime};
+use tauri::api::process::Command;
+use tauri::utils::platform::current_exe;
+
+/// 内置的 sidecar 内核
+pub const BUILTIN_CORES: [&str; 2] = ["verge-mihomo", "verge-mihomo-alpha"];
+
+/// the detected versions -> (binary path, modified time)
+static VERSIONS: Lazy<Mutex<HashMap<PathBuf, (SystemTime, CoreVersion)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// the version of the current core -> (core name, version)
+/// refreshed when the core is changed, registered or updated
+static CURRENT_VERSION: Lazy<Mutex<Option<(String, Option<CoreVersion>)>>> =
+    Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CoreKind {
+    Mihomo,
+    /// the original clash or clash premium
+    Clash,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CoreVersion {
+    pub kind: CoreKind,
+    /// like `v1.18.9` or `alpha-e1bf3ab`
+    pub version: String,
+    pub alpha: bool,
+}
+
+impl CoreVersion {
+    /// parse the output of `-v`, like
+    /// `Mihomo Meta v1.18.9 linux amd64 with go1.23.1 Sun Sep 29 2024`
+    /// `Clash Meta alpha-e1bf3ab darwin arm64 with go1.21.1`
+    /// `Clash v1.18.0 linux amd64 with go1.20.4`
+    pub fn parse(output: &str) -> Option<Self> {
+        let line = output.lines().map(str::trim).find(|l| !l.is_empty())?;
+        let mut words = line.split_whitespace();
+        let first = words.next()?.to_lowercase();
+        let second = words.next()?;
+
+        let (kind, version) = match first.as_str() {
+            _ if second.eq_ignore_ascii_case("meta") => (CoreKind::Mihomo, words.next()?),
+            "mihomo" => (CoreKind::Mihomo, second),
+            "clash" => (CoreKind::Clash, second),
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            version: version.into(),
+            alpha: version.to_lowercase().contains("alpha"),
+        })
+    }
+
+    /// guess from the name of the builtin sidecar
+    pub fn from_sidecar(name: &str) -> Option<Self> {
+        let (kind, alpha) = match name {
+            "verge-mihomo" => (CoreKind::Mihomo, false),
+            "verge-mihomo-alpha" => (CoreKind::Mihomo, true),
+            "clash" => (CoreKind::Clash, false),
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            version: "unknown".into(),
+            alpha,
+        })
+    }
+}
+
+/// the core shown in the core list
+#[derive(Debug, Clone, Serialize)]
+pub struct CoreInfo {
+    pub name: String,
+    pub path: Option<String>,
+    pub custom: bool,
+    pub version: Option<CoreVersion>,
+}
+
+/// the builtin cores and the cores added by the user
+pub fn list_cores() -> Vec<CoreInfo> {
+    let customs = { Config::verge().latest().custom_cores.clone() };
+    let builtins = BUILTIN_CORES
+        .iter()
+        .map(|name| CoreBinary::Sidecar(name.to_string()));
+    let customs = customs
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| CoreBinary::Custom {
+            name: c.name,
+            path: PathBuf::from(c.path),
+        });
+
+    builtins
+        .chain(customs)
+        .map(|core| CoreInfo {
+            name: core.name().into(),
+            path: core.path().ok().map(|p| p.display().to_string()),
+            custom: matches!(core, CoreBinary::Custom { .. }),
+            version: core.version(),
+        })
+        .collect()
+}
+
+/// the binary of the core, the builtin sidecar or the one added by the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreBinary {
+    Sidecar(String),
+    Custom { name: String, path: PathBuf },
+}
+
+impl CoreBinary {
+    /// find the core by the name used in `clash_core`
+    pub fn resolve(name: &str) -> Result<Self> {
+        if BUILTIN_CORES.contains(&name) {
+            return Ok(Self::Sidecar(name.into()));
+        }
+
+        let cores = Config::verge().latest().custom_cores.clone();
+        match cores
+            .unwrap_or_default()
+            .into_iter()
+            .find(|c| c.name == name)
+        {
+            Some(core) => Ok(Self::Custom {
+                name: core.name,
+                path: PathBuf::from(core.path),
+            }),
+            None => bail!("invalid clash core name \"{name}\""),
+        }
+    }
+
+    pub fn current() -> Result<Self> {
+        let clash_core = { Config::verge().latest().clash_core.clone() };
+        Self::resolve(&clash_core.unwrap_or("verge-mihomo".into()))
+    }
+
+    /// the cached version of the current core, used by every generation of the config
+    pub fn current_version() -> Option<CoreVersion> {
+        let name = { Config::verge().latest().clash_core.clone() };
+        let name = name.unwrap_or("verge-mihomo".into());
+        if let Some((cached, version)) = CURRENT_VERSION.lock().as_ref() {
+            if *cached == name {
+                return version.clone();
+            }
+        }
+        Self::refresh_current_version()
+    }
+
+    /// detect the version of the current core again
+    pub fn refresh_current_version() -> Option<CoreVersion> {
+        let core = Self::current().ok()?;
+        let version = core.version();
+        *CURRENT_VERSION.lock() = Some((core.name().into(), version.clone()));
+        version
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Sidecar(name) => name,
+            Self::Custom { name, .. } => name,
+        }
+    }
+
+    pub fn path(&self) -> Result<PathBuf> {
+        match self {
+            Self::Sidecar(name) => {
+                let ext = if cfg!(windows) { ".exe" } else { "" };
+                Ok(current_exe()?.with_file_name(format!("{name}{ext}")))
+            }
+            Self::Custom { path, .. } => Ok(path.clone()),
+        }
+    }
+
+    pub fn command(&self) -> Result<Command> {
+        match self {
+            Self::Sidecar(name) => Ok(Command::new_sidecar(name)?),
+            Self::Custom { path, .. } => Ok(Command::new(dirs::path_to_str(path)?)),
+        }
+    }
+
+    /// run `-v` and parse the output
+    pub fn detect_version(&self) -> Result<CoreVersion> {
+        let output = self.command()?.args(["-v"]).output()?;
+        if !output.status.success() {
+            bail!(
+                "failed to get the version of \"{}\": {}",
+                self.name(),
+                output.stderr
+            );
+        }
+        CoreVersion::parse(&output.stdout)
+            .with_context(|| format!("unknown core version \"{}\"", output.stdout.trim()))
+    }
+
+    /// the cached version, detect again if the binary is replaced
+    /// fall back to the sidecar name if the detection fails
+    pub fn version(&self) -> Option<CoreVersion> {
+        let cached = || -> Result<CoreVersion> {
+            let path = self.path()?;
+            let modified = std::fs::metadata(&path)?.modified()?;
+            if let Some((time, version)) = VERSIONS.lock().get(&path) {
+                if *time == modified {
+                    return Ok(version.clone());
+                }
+            }
+            let version = self.detect_version()?;
+            VERSIONS.lock().insert(path, (modified, version.clone()));
+            Ok(version)
+        };
+
+        match (cached(), self) {
+            (Ok(version), _) => Some(version),
+            (Err(err), Self::Sidecar(name)) => {
+                log::debug!(target: "app", "{err}");
+                CoreVersion::from_sidecar(name)
+            }
+            (Err(err), Self::Custom { .. }) => {
+                log::error!(target: "app", "{err}");
+                None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_core_version() {
+    let parse = |s: &str| CoreVersion::parse(s).map(|v| (v.kind, v.version, v.alpha));
+
+    assert_eq!(
+        parse("Mihomo Meta v1.18.9 linux amd64 with go1.23.1 Sun Sep 29 05:54:32 UTC 2024\nUse tags: with_gvisor\n"),
+        Some((CoreKind::Mihomo, "v1.18.9".into(), false))
+    );
+    assert_eq!(
+        parse("Mihomo Meta alpha-e1bf3ab darwin arm64 with go1.23.1"),
+        Some((CoreKind::Mihomo, "alpha-e1bf3ab".into(), true))
+    );
+    assert_eq!(
+        parse("\nClash Meta v1.16.0 windows amd64 with go1.21.1"),
+        Some((CoreKind::Mihomo, "v1.16.0".into(), false))
+    );
+    assert_eq!(
+        parse("Clash v1.18.0 linux amd64 with go1.20.4"),
+        Some((CoreKind::Clash, "v1.18.0".into(), false))
+    );
+    assert_eq!(
+        parse("mihomo v1.19.0"),
+        Some((CoreKind::Mihomo, "v1.19.0".into(), false))
+    );
+    assert_eq!(parse("flag provided but not defined: -v"), None);
+    assert_eq!(parse(""), None);
+
+    let alpha = CoreVersion::from_sidecar("verge-mihomo-alpha").unwrap();
+    assert!(alpha.alpha);
+    assert_eq!(CoreVersion::from_sidecar("self-built"), None);
+}
@@ -1,114 +1,676 @@
-use crate::config::Config;
-use anyhow::{bail, Result};
-use reqwest::header::HeaderMap;
-use serde::{Deserialize, Serialize};
+use crate::config::{ClashInfo, Config};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::{header::HeaderMap, Client, Method, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_yaml::Mapping;
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
-/// POST /restart
-pub async fn restart_core() -> Result<()> {
-    let (url, headers) = clash_client_info()?;
-    let url = format!("{url}/restart");
+/// 所有请求共用一个 client
+static CLIENT: Lazy<Client> = Lazy::new(|| {
+    reqwest::ClientBuilder::new()
+        .no_proxy()
+        .build()
+        .expect("failed to build the clash api client")
+});
+
+const DEFAULT_TEST_URL: &str = "https://www.gstatic.com/generate_204";
+
+/// the error of the external controller api
+#[derive(Debug)]
+pub enum ClashApiError {
+    /// failed to send the request, e.g. the core is not running
+    Request(reqwest::Error),
+    /// the controller responds with a non-success status
+    Status { status: u16, message: String },
+    /// failed to parse the response body
+    Decode(reqwest::Error),
+}
+
+impl ClashApiError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClashApiError::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
 
-    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
-    let _ = client.post(&url).headers(headers.clone()).send().await?;
-    Ok(())
+impl fmt::Display for ClashApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClashApiError::Request(err) => write!(f, "failed to request clash api: {err}"),
+            ClashApiError::Status { status, message } => {
+                write!(f, "clash api responds with status \"{status}\": {message}")
+            }
+            ClashApiError::Decode(err) => write!(f, "failed to parse clash api response: {err}"),
+        }
+    }
 }
 
+impl std::error::Error for ClashApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClashApiError::Request(err) | ClashApiError::Decode(err) => Some(err),
+            ClashApiError::Status { .. } => None,
+        }
+    }
+}
+
+pub type ApiResult<T> = std::result::Result<T, ClashApiError>;
+
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct ClashBasicConfig {
     /// only use tun config for now
     pub tun: Mapping,
 }
-/// GET /configs
-pub async fn get_configs() -> Result<ClashBasicConfig> {
-    let (url, headers) = clash_client_info()?;
-    let url = format!("{url}/configs");
 
-    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
-    let response = client.get(&url).headers(headers.clone()).send().await?;
-    Ok(response.json::<ClashBasicConfig>().await?)
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct DelayRes {
+    message: Option<String>,
+    delay: Option<u64>,
 }
 
-/// PUT /configs
-/// path 是绝对路径
-pub async fn put_configs(path: &str) -> Result<()> {
-    let (url, headers) = clash_client_info()?;
-    let url = format!("{url}/configs?force=true");
-    let data = HashMap::from([("path", path)]);
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ClashVersion {
+    #[serde(default)]
+    pub meta: bool,
+    pub version: String,
+}
 
-    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
-    let response = client.put(&url).headers(headers).json(&data).send().await?;
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct DelayHistory {
+    pub time: String,
+    pub delay: u64,
+}
 
-    match response.status().as_u16() {
-        204 => Ok(()),
-        status => {
-            bail!("failed to put configs with status \"{status}\"")
-        }
-    }
+/// proxy or proxy group
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyItem {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ptype: String,
+    #[serde(default)]
+    pub udp: bool,
+    #[serde(default)]
+    pub history: Vec<DelayHistory>,
+    /// the proxies of the group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub all: Option<Vec<String>>,
+    /// the selected proxy of the group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub now: Option<String>,
+    /// the fixed proxy of the url-test / fallback group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fixed: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hidden: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alive: Option<bool>,
 }
 
-/// PATCH /configs
-pub async fn patch_configs(config: &Mapping) -> Result<()> {
-    let (url, headers) = clash_client_info()?;
-    let url = format!("{url}/configs");
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionInfo {
+    #[serde(default)]
+    pub upload: u64,
+    #[serde(default)]
+    pub download: u64,
+    #[serde(default)]
+    pub total: u64,
+    #[serde(default)]
+    pub expire: u64,
+}
 
-    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
-    let _ = client
-        .patch(&url)
-        .headers(headers.clone())
-        .json(config)
-        .send()
-        .await?;
-    Ok(())
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyProvider {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ptype: String,
+    pub vehicle_type: String,
+    #[serde(default)]
+    pub proxies: Vec<ProxyItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription_info: Option<SubscriptionInfo>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
-pub struct DelayRes {
-    message: Option<String>,
-    delay: Option<u64>,
+#[serde(rename_all = "camelCase")]
+pub struct RuleProvider {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ptype: String,
+    pub vehicle_type: String,
+    pub behavior: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub rule_count: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
 }
 
-/// GET /proxies/{name}/delay
-/// 获取代理延迟
-pub async fn get_proxy_delay(
-    name: String,
-    test_url: Option<String>,
-    timeout: i32,
-) -> Result<DelayRes> {
-    let (url, headers) = clash_client_info()?;
-    let url = format!("{url}/proxies/{name}/delay");
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct RuleItem {
+    #[serde(rename = "type")]
+    pub rtype: String,
+    pub payload: String,
+    pub proxy: String,
+    /// the rule count of the rule set, `-1` for the others
+    #[serde(default)]
+    pub size: i64,
+}
 
-    let default_url = "https://www.gstatic.com/generate_204";
-    let test_url = test_url
-        .map(|s| if s.is_empty() { default_url.into() } else { s })
-        .unwrap_or(default_url.into());
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionMetadata {
+    pub network: String,
+    #[serde(rename = "type")]
+    pub ctype: String,
+    #[serde(rename = "sourceIP")]
+    pub source_ip: String,
+    #[serde(rename = "destinationIP")]
+    pub destination_ip: String,
+    pub source_port: String,
+    pub destination_port: String,
+    #[serde(default)]
+    pub host: String,
+    #[serde(default)]
+    pub dns_mode: String,
+    #[serde(default)]
+    pub process: String,
+    #[serde(default)]
+    pub process_path: String,
+    #[serde(default)]
+    pub special_proxy: String,
+    #[serde(default)]
+    pub special_rules: String,
+    #[serde(default)]
+    pub remote_destination: String,
+    #[serde(default)]
+    pub sniff_host: String,
+    #[serde(default)]
+    pub inbound_name: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Connection {
+    pub id: String,
+    pub metadata: ConnectionMetadata,
+    pub upload: u64,
+    pub download: u64,
+    pub start: String,
+    #[serde(default)]
+    pub chains: Vec<String>,
+    #[serde(default)]
+    pub rule: String,
+    #[serde(default)]
+    pub rule_payload: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Connections {
+    pub download_total: u64,
+    pub upload_total: u64,
+    #[serde(default)]
+    pub connections: Vec<Connection>,
+    #[serde(default)]
+    pub memory: u64,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct DnsAnswer {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub rtype: u16,
+    #[serde(rename = "TTL")]
+    pub ttl: u32,
+    pub data: String,
+}
+
+/// GET /dns/query
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct DnsQuery {
+    #[serde(rename = "Status")]
+    pub status: u16,
+    #[serde(rename = "Question", default)]
+    pub question: Vec<DnsQuestion>,
+    #[serde(rename = "Answer", default, skip_serializing_if = "Vec::is_empty")]
+    pub answer: Vec<DnsAnswer>,
+}
+
+/// GET /traffic, bytes per second
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TrafficRes {
+    pub up: u64,
+    pub down: u64,
+}
+
+/// GET /memory
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct MemoryRes {
+    pub inuse: u64,
+    #[serde(default)]
+    pub oslimit: u64,
+}
+
+/// GET /logs
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct LogRes {
+    #[serde(rename = "type")]
+    pub log_type: String,
+    pub payload: String,
+}
+
+#[derive(Deserialize)]
+struct ProxiesRes<T> {
+    proxies: T,
+}
+
+#[derive(Deserialize)]
+struct ProvidersRes<T> {
+    providers: HashMap<String, T>,
+}
+
+#[derive(Deserialize)]
+struct RulesRes {
+    rules: Vec<RuleItem>,
+}
+
+#[derive(Deserialize)]
+struct MessageRes {
+    message: String,
+}
+
+/// the external controller of the core
+#[derive(Debug, Clone)]
+pub struct ClashApi {
+    server: String,
+    headers: HeaderMap,
+}
+
+impl ClashApi {
+    /// `server` like `http://127.0.0.1:9097`
+    pub fn new(server: &str, secret: Option<&str>) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type", "application/json".parse()?);
+
+        if let Some(secret) = secret {
+            let secret = format!("Bearer {}", secret).parse()?;
+            headers.insert("Authorization", secret);
+        }
+
+        Ok(Self {
+            server: server.trim_end_matches('/').to_string(),
+            headers,
+        })
+    }
+
+    /// 根据clash info获取clash服务地址和请求头
+    pub fn current() -> Result<Self> {
+        let client = { Config::clash().data().get_client_info() };
+        Self::from_info(&client)
+    }
+
+    /// 使用还没提交的 clash info，内核用新的配置启动或重载后使用
+    pub fn latest() -> Result<Self> {
+        let client = { Config::clash().latest().get_client_info() };
+        Self::from_info(&client)
+    }
+
+    fn from_info(client: &ClashInfo) -> Result<Self> {
+        let server = format!("http://{}", client.server);
+        Self::new(&server, client.secret.as_deref())
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let url = format!("{}{path}", self.server);
+        CLIENT.request(method, url).headers(self.headers.clone())
+    }
+
+    async fn send(&self, builder: RequestBuilder) -> ApiResult<reqwest::Response> {
+        let response = builder.send().await.map_err(ClashApiError::Request)?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let text = response.text().await.unwrap_or_default();
+        let message = match serde_json::from_str::<MessageRes>(&text) {
+            Ok(res) => res.message,
+            Err(_) => text,
+        };
+        Err(ClashApiError::Status {
+            status: status.as_u16(),
+            message,
+        })
+    }
+
+    async fn send_json<T: DeserializeOwned>(&self, builder: RequestBuilder) -> ApiResult<T> {
+        let response = self.send(builder).await?;
+        response.json::<T>().await.map_err(ClashApiError::Decode)
+    }
+
+    async fn send_empty(&self, builder: RequestBuilder) -> ApiResult<()> {
+        self.send(builder).await.map(|_| ())
+    }
+
+    /// GET /version
+    pub async fn version(&self) -> ApiResult<ClashVersion> {
+        self.send_json(self.request(Method::GET, "/version")).await
+    }
 
-    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
-    let response = client
-        .get(&url)
-        .headers(headers)
-        .query(&[("timeout", &format!("{timeout}")), ("url", &test_url)])
-        .send()
-        .await?;
-    Ok(response.json::<DelayRes>().await?)
+    /// POST /restart
+    pub async fn restart(&self) -> ApiResult<()> {
+        self.send_empty(self.request(Method::POST, "/restart"))
+            .await
+    }
+
+    /// GET /configs
+    pub async fn get_configs(&self) -> ApiResult<ClashBasicConfig> {
+        self.send_json(self.request(Method::GET, "/configs")).await
+    }
+
+    /// PUT /configs
+    /// path 是绝对路径
+    pub async fn put_configs(&self, path: &str) -> ApiResult<()> {
+        let data = HashMap::from([("path", path)]);
+        let builder = self.request(Method::PUT, "/configs?force=true");
+        self.send_empty(builder.json(&data)).await
+    }
+
+    /// PATCH /configs
+    pub async fn patch_configs(&self, config: &Mapping) -> ApiResult<()> {
+        let builder = self.request(Method::PATCH, "/configs");
+        self.send_empty(builder.json(config)).await
+    }
+
+    /// GET /proxies
+    pub async fn get_proxies(&self) -> ApiResult<HashMap<String, ProxyItem>> {
+        let res: ProxiesRes<_> = self
+            .send_json(self.request(Method::GET, "/proxies"))
+            .await?;
+        Ok(res.proxies)
+    }
+
+    /// GET /proxies/{name}
+    pub async fn get_proxy(&self, name: &str) -> ApiResult<ProxyItem> {
+        let path = format!("/proxies/{}", encode(name));
+        self.send_json(self.request(Method::GET, &path)).await
+    }
+
+    /// PUT /proxies/{group}
+    /// 选择代理组的节点
+    pub async fn select_proxy(&self, group: &str, name: &str) -> ApiResult<()> {
+        let path = format!("/proxies/{}", encode(group));
+        let data = HashMap::from([("name", name)]);
+        self.send_empty(self.request(Method::PUT, &path).json(&data))
+            .await
+    }
+
+    /// GET /proxies/{name}/delay
+    pub async fn get_proxy_delay(
+        &self,
+        name: &str,
+        test_url: Option<&str>,
+        timeout: i32,
+    ) -> ApiResult<u64> {
+        #[derive(Deserialize)]
+        struct Delay {
+            delay: u64,
+        }
+
+        let path = format!("/proxies/{}/delay", encode(name));
+        let query = delay_query(test_url, timeout);
+        let builder = self.request(Method::GET, &path).query(&query);
+        let res: Delay = self.send_json(builder).await?;
+        Ok(res.delay)
+    }
+
+    /// GET /group/{name}
+    pub async fn get_group(&self, name: &str) -> ApiResult<ProxyItem> {
+        let path = format!("/group/{}", encode(name));
+        self.send_json(self.request(Method::GET, &path)).await
+    }
+
+    /// GET /providers/proxies
+    pub async fn get_proxy_providers(&self) -> ApiResult<HashMap<String, ProxyProvider>> {
+        let builder = self.request(Method::GET, "/providers/proxies");
+        let res: ProvidersRes<_> = self.send_json(builder).await?;
+        Ok(res.providers)
+    }
+
+    /// GET /connections
+    pub async fn get_connections(&self) -> ApiResult<Connections> {
+        self.send_json(self.request(Method::GET, "/connections"))
+            .await
+    }
+
+    /// DELETE /connections
+    pub async fn close_all_connections(&self) -> ApiResult<()> {
+        self.send_empty(self.request(Method::DELETE, "/connections"))
+            .await
+    }
+
+    /// DELETE /connections/{id}
+    pub async fn close_connection(&self, id: &str) -> ApiResult<()> {
+        let path = format!("/connections/{}", encode(id));
+        self.send_empty(self.request(Method::DELETE, &path)).await
+    }
+
+    /// the streaming endpoints like `/traffic`, `/memory` and `/logs`
+    /// without the websocket upgrade, the core writes one json per line
+    /// returns when the core closes the stream
+    pub async fn stream<T, F>(&self, path: &str, mut f: F) -> ApiResult<()>
+    where
+        T: DeserializeOwned,
+        F: FnMut(T),
+    {
+        let mut response = self.send(self.request(Method::GET, path)).await?;
+        let mut buf: Vec<u8> = vec![];
+
+        while let Some(chunk) = response.chunk().await.map_err(ClashApiError::Request)? {
+            buf.extend_from_slice(&chunk);
+
+            while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+                let line = buf.drain(..=pos).collect::<Vec<_>>();
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                match serde_json::from_slice::<T>(&line) {
+                    Ok(item) => f(item),
+                    Err(err) => log::warn!(target: "app", "failed to parse {path} stream: {err}"),
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
-/// 根据clash info获取clash服务地址和请求头
-fn clash_client_info() -> Result<(String, HeaderMap)> {
-    let client = { Config::clash().data().get_client_info() };
+/// the endpoints which are not used by the app yet
+#[allow(dead_code)]
+impl ClashApi {
+    /// DELETE /proxies/{group}
+    /// 取消 url-test / fallback 代理组固定的节点
+    pub async fn unfix_proxy(&self, group: &str) -> ApiResult<()> {
+        let path = format!("/proxies/{}", encode(group));
+        self.send_empty(self.request(Method::DELETE, &path)).await
+    }
+
+    /// GET /group
+    pub async fn get_groups(&self) -> ApiResult<Vec<ProxyItem>> {
+        let res: ProxiesRes<_> = self.send_json(self.request(Method::GET, "/group")).await?;
+        Ok(res.proxies)
+    }
+
+    /// GET /group/{name}/delay
+    /// 测试代理组内所有节点的延迟，超时的节点不会出现在结果里
+    pub async fn get_group_delay(
+        &self,
+        name: &str,
+        test_url: Option<&str>,
+        timeout: i32,
+    ) -> ApiResult<HashMap<String, u64>> {
+        let path = format!("/group/{}/delay", encode(name));
+        let query = delay_query(test_url, timeout);
+        self.send_json(self.request(Method::GET, &path).query(&query))
+            .await
+    }
+
+    /// GET /providers/proxies/{name}
+    pub async fn get_proxy_provider(&self, name: &str) -> ApiResult<ProxyProvider> {
+        let path = format!("/providers/proxies/{}", encode(name));
+        self.send_json(self.request(Method::GET, &path)).await
+    }
+
+    /// PUT /providers/proxies/{name}
+    pub async fn update_proxy_provider(&self, name: &str) -> ApiResult<()> {
+        let path = format!("/providers/proxies/{}", encode(name));
+        self.send_empty(self.request(Method::PUT, &path)).await
+    }
+
+    /// GET /providers/proxies/{name}/healthcheck
+    pub async fn healthcheck_proxy_provider(&self, name: &str) -> ApiResult<()> {
+        let path = format!("/providers/proxies/{}/healthcheck", encode(name));
+        self.send_empty(self.request(Method::GET, &path)).await
+    }
+
+    /// GET /providers/rules
+    pub async fn get_rule_providers(&self) -> ApiResult<HashMap<String, RuleProvider>> {
+        let builder = self.request(Method::GET, "/providers/rules");
+        let res: ProvidersRes<_> = self.send_json(builder).await?;
+        Ok(res.providers)
+    }
+
+    /// PUT /providers/rules/{name}
+    pub async fn update_rule_provider(&self, name: &str) -> ApiResult<()> {
+        let path = format!("/providers/rules/{}", encode(name));
+        self.send_empty(self.request(Method::PUT, &path)).await
+    }
+
+    /// GET /rules
+    pub async fn get_rules(&self) -> ApiResult<Vec<RuleItem>> {
+        let res: RulesRes = self.send_json(self.request(Method::GET, "/rules")).await?;
+        Ok(res.rules)
+    }
+
+    /// GET /dns/query
+    /// `qtype` like `A`, `AAAA`
+    pub async fn dns_query(&self, name: &str, qtype: &str) -> ApiResult<DnsQuery> {
+        let builder = self.request(Method::GET, "/dns/query");
+        let builder = builder.query(&[("name", name), ("type", qtype)]);
+        self.send_json(builder).await
+    }
+
+    /// POST /cache/fakeip/flush
+    pub async fn flush_fakeip_cache(&self) -> ApiResult<()> {
+        self.send_empty(self.request(Method::POST, "/cache/fakeip/flush"))
+            .await
+    }
+
+    /// POST /cache/dns/flush
+    pub async fn flush_dns_cache(&self) -> ApiResult<()> {
+        self.send_empty(self.request(Method::POST, "/cache/dns/flush"))
+            .await
+    }
 
-    let server = format!("http://{}", client.server);
+    /// POST /upgrade
+    /// 内核自己下载新版本并重启
+    pub async fn upgrade_core(&self) -> ApiResult<()> {
+        self.send_empty(self.request(Method::POST, "/upgrade"))
+            .await
+    }
 
-    let mut headers = HeaderMap::new();
-    headers.insert("Content-Type", "application/json".parse()?);
+    /// POST /upgrade/ui
+    pub async fn upgrade_ui(&self) -> ApiResult<()> {
+        self.send_empty(self.request(Method::POST, "/upgrade/ui"))
+            .await
+    }
 
-    if let Some(secret) = client.secret {
-        let secret = format!("Bearer {}", secret).parse()?;
-        headers.insert("Authorization", secret);
+    /// POST /upgrade/geo
+    pub async fn upgrade_geo(&self) -> ApiResult<()> {
+        self.send_empty(self.request(Method::POST, "/upgrade/geo"))
+            .await
     }
+}
+
+/// 代理名称可能包含 `/`、空格等字符
+fn encode(segment: &str) -> String {
+    utf8_percent_encode(segment, NON_ALPHANUMERIC).to_string()
+}
+
+fn delay_query(test_url: Option<&str>, timeout: i32) -> [(&'static str, String); 2] {
+    let test_url = test_url
+        .filter(|s| !s.is_empty())
+        .unwrap_or(DEFAULT_TEST_URL);
+    [
+        ("timeout", timeout.to_string()),
+        ("url", test_url.to_string()),
+    ]
+}
+
+/// POST /restart
+pub async fn restart_core() -> Result<()> {
+    Ok(ClashApi::current()?.restart().await?)
+}
+
+/// GET /configs
+pub async fn get_configs() -> Result<ClashBasicConfig> {
+    Ok(ClashApi::current()?.get_configs().await?)
+}
+
+/// PUT /configs
+/// path 是绝对路径
+pub async fn put_configs(path: &str) -> Result<()> {
+    Ok(ClashApi::current()?.put_configs(path).await?)
+}
+
+/// PATCH /configs
+pub async fn patch_configs(config: &Mapping) -> Result<()> {
+    Ok(ClashApi::current()?.patch_configs(config).await?)
+}
 
-    Ok((server, headers))
+/// GET /proxies/{name}/delay
+/// 获取代理延迟，超时等错误放在 `message` 里
+pub async fn get_proxy_delay(
+    name: String,
+    test_url: Option<String>,
+    timeout: i32,
+) -> Result<DelayRes> {
+    let api = ClashApi::current()?;
+    match api
+        .get_proxy_delay(&name, test_url.as_deref(), timeout)
+        .await
+    {
+        Ok(delay) => Ok(DelayRes {
+            message: None,
+            delay: Some(delay),
+        }),
+        Err(ClashApiError::Status { message, .. }) => Ok(DelayRes {
+            message: Some(message),
+            delay: None,
+        }),
+        Err(err) => Err(err.into()),
+    }
 }
 
 /// 缩短clash的日志
@@ -171,3 +733,248 @@ fn test_parse_check_output() {
 
     assert_eq!(res1, res3);
 }
+
+#[tokio::test]
+async fn test_clash_api_mock() {
+    use serde_json::json;
+    use warp::{http::StatusCode, Filter, Reply};
+
+    let auth = warp::header::exact("authorization", "Bearer secret");
+    let version = warp::path!("version")
+        .map(|| warp::reply::json(&json!({"meta": true, "version": "v1.18.9"})));
+    let proxies = warp::path!("proxies").and(warp::get()).map(|| {
+        warp::reply::json(&json!({ "proxies": {
+            "GLOBAL": { "name": "GLOBAL", "type": "Selector", "udp": true, "history": [], "all": ["a/b", "DIRECT"], "now": "a/b" },
+            "a/b": { "name": "a/b", "type": "Shadowsocks", "udp": true, "history": [{ "time": "2024-01-01T00:00:00Z", "delay": 100 }] }
+        }}))
+    });
+    let select = warp::path!("proxies" / String)
+        .and(warp::put())
+        .and(warp::body::json())
+        .map(|group: String, body: HashMap<String, String>| {
+            match (group.as_str(), body.get("name").map(String::as_str)) {
+                ("GLOBAL", Some("a/b")) => StatusCode::NO_CONTENT.into_response(),
+                _ => warp::reply::with_status(
+                    warp::reply::json(
+                        &json!({"message": "Selector update error: proxy not exist"}),
+                    ),
+                    StatusCode::BAD_REQUEST,
+                )
+                .into_response(),
+            }
+        });
+    let delay = warp::path!("proxies" / String / "delay").map(|name: String| {
+        // the path segment is encoded
+        let name = percent_encoding::percent_decode_str(&name).decode_utf8_lossy();
+        match name.as_ref() {
+            "a/b" => warp::reply::json(&json!({"delay": 100})).into_response(),
+            _ => warp::reply::with_status(
+                warp::reply::json(&json!({"message": "Timeout"})),
+                StatusCode::GATEWAY_TIMEOUT,
+            )
+            .into_response(),
+        }
+    });
+    let connections = warp::path!("connections").and(warp::get()).map(|| {
+        warp::reply::json(&json!({
+            "downloadTotal": 10, "uploadTotal": 20, "memory": 1024,
+            "connections": [{
+                "id": "conn-1", "upload": 1, "download": 2, "start": "2024-01-01T00:00:00Z",
+                "chains": ["a/b", "GLOBAL"], "rule": "Match", "rulePayload": "",
+                "metadata": {
+                    "network": "tcp", "type": "HTTP", "sourceIP": "127.0.0.1", "destinationIP": "1.1.1.1",
+                    "sourcePort": "5000", "destinationPort": "443", "host": "example.com"
+                }
+            }]
+        }))
+    });
+    let traffic = warp::path!("traffic").map(|| {
+        // the json may be split into different chunks
+        let chunks = [
+            "{\"up\":1,\"down\":2}\n{\"up\":3,",
+            "\"down\":4}\n\n",
+            "{\"up\":5,\"down\":6}\n",
+        ];
+        let (mut sender, body) = warp::hyper::Body::channel();
+        tokio::spawn(async move {
+            for chunk in chunks {
+                let _ = sender.send_data(chunk.into()).await;
+            }
+        });
+        warp::http::Response::new(body)
+    });
+    let close = warp::path!("connections" / String)
+        .and(warp::delete())
+        .map(|_| StatusCode::NO_CONTENT);
+    let close_all = warp::path!("connections")
+        .and(warp::delete())
+        .map(|| StatusCode::NO_CONTENT);
+    let unfix = warp::path!("proxies" / String)
+        .and(warp::delete())
+        .map(|_| StatusCode::NO_CONTENT);
+    let groups = warp::path!("group").map(|| {
+        warp::reply::json(&json!({ "proxies": [
+            { "name": "auto", "type": "URLTest", "all": ["a/b"], "now": "a/b", "fixed": "a/b" }
+        ]}))
+    });
+    let group = warp::path!("group" / String).map(|name: String| {
+        warp::reply::json(&json!({ "name": name, "type": "URLTest", "all": ["a/b"], "now": "a/b" }))
+    });
+    let group_delay =
+        warp::path!("group" / String / "delay").map(|_| warp::reply::json(&json!({"a/b": 100})));
+    let proxy_providers = warp::path!("providers" / "proxies").map(|| {
+        warp::reply::json(&json!({ "providers": {
+            "sub": { "name": "sub", "type": "Proxy", "vehicleType": "HTTP", "proxies": [] }
+        }}))
+    });
+    let proxy_provider = warp::path!("providers" / "proxies" / String)
+        .and(warp::get())
+        .map(|name: String| {
+            warp::reply::json(&json!({ "name": name, "type": "Proxy", "vehicleType": "HTTP" }))
+        });
+    let update_proxy_provider = warp::path!("providers" / "proxies" / String)
+        .and(warp::put())
+        .map(|_| StatusCode::NO_CONTENT);
+    let healthcheck = warp::path!("providers" / "proxies" / String / "healthcheck")
+        .map(|_| StatusCode::NO_CONTENT);
+    let rule_providers = warp::path!("providers" / "rules").map(|| {
+        warp::reply::json(&json!({ "providers": {
+            "reject": { "name": "reject", "type": "Rule", "vehicleType": "HTTP", "behavior": "Domain",
+                "format": "YamlRule", "ruleCount": 3 }
+        }}))
+    });
+    let update_rule_provider = warp::path!("providers" / "rules" / String)
+        .and(warp::put())
+        .map(|_| StatusCode::NO_CONTENT);
+    let rules = warp::path!("rules").map(|| {
+        warp::reply::json(&json!({ "rules": [
+            { "type": "RuleSet", "payload": "reject", "proxy": "REJECT", "size": 3 },
+            { "type": "Match", "payload": "", "proxy": "DIRECT" }
+        ]}))
+    });
+    let dns_query = warp::path!("dns" / "query")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let name = query.get("name").cloned().unwrap_or_default();
+            warp::reply::json(&json!({
+                "Status": 0,
+                "Question": [{ "Name": format!("{name}."), "Qtype": 1, "Qclass": 1 }],
+                "Answer": [{ "name": format!("{name}."), "type": 1, "TTL": 60, "data": "1.1.1.1" }]
+            }))
+        });
+    let flush = warp::path!("cache" / String / "flush")
+        .and(warp::post())
+        .map(|_| StatusCode::NO_CONTENT);
+    let upgrade_core = warp::path!("upgrade")
+        .and(warp::post())
+        .map(|| StatusCode::OK);
+    let upgrade = warp::path!("upgrade" / String)
+        .and(warp::post())
+        .map(|_| StatusCode::OK);
+    let routes = auth.and(
+        version
+            .or(proxies)
+            .or(select)
+            .or(delay)
+            .or(connections)
+            .or(traffic)
+            .or(close)
+            .or(close_all)
+            .or(unfix)
+            .or(groups)
+            .or(group)
+            .or(group_delay)
+            .boxed()
+            .or(proxy_providers)
+            .or(proxy_provider)
+            .or(update_proxy_provider)
+            .or(healthcheck)
+            .or(rule_providers)
+            .or(update_rule_provider)
+            .or(rules)
+            .or(dns_query)
+            .or(flush)
+            .or(upgrade_core)
+            .or(upgrade),
+    );
+
+    let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let api = ClashApi::new(&format!("http://{addr}/"), Some("secret")).unwrap();
+
+    let version = api.version().await.unwrap();
+    assert!(version.meta);
+    assert_eq!(version.version, "v1.18.9");
+
+    let proxies = api.get_proxies().await.unwrap();
+    assert_eq!(proxies["GLOBAL"].now.as_deref(), Some("a/b"));
+    assert_eq!(proxies["a/b"].history[0].delay, 100);
+
+    api.select_proxy("GLOBAL", "a/b").await.unwrap();
+    let err = api.select_proxy("GLOBAL", "missing").await.unwrap_err();
+    assert_eq!(err.status(), Some(400));
+    assert!(err.to_string().contains("proxy not exist"));
+
+    assert_eq!(api.get_proxy_delay("a/b", None, 5000).await.unwrap(), 100);
+    let err = api.get_proxy_delay("c", None, 5000).await.unwrap_err();
+    assert!(
+        matches!(err, ClashApiError::Status { status: 504, ref message } if message == "Timeout")
+    );
+
+    let connections = api.get_connections().await.unwrap();
+    assert_eq!(connections.download_total, 10);
+    assert_eq!(connections.connections[0].metadata.host, "example.com");
+    api.close_connection("conn-1").await.unwrap();
+    api.close_all_connections().await.unwrap();
+
+    api.unfix_proxy("auto").await.unwrap();
+    let groups = api.get_groups().await.unwrap();
+    assert_eq!(groups[0].fixed.as_deref(), Some("a/b"));
+    assert_eq!(api.get_group("auto").await.unwrap().name, "auto");
+    let delay = api.get_group_delay("auto", None, 5000).await.unwrap();
+    assert_eq!(delay["a/b"], 100);
+
+    let providers = api.get_proxy_providers().await.unwrap();
+    assert_eq!(providers["sub"].vehicle_type, "HTTP");
+    assert_eq!(api.get_proxy_provider("sub").await.unwrap().name, "sub");
+    api.update_proxy_provider("sub").await.unwrap();
+    api.healthcheck_proxy_provider("sub").await.unwrap();
+
+    let providers = api.get_rule_providers().await.unwrap();
+    assert_eq!(providers["reject"].rule_count, 3);
+    api.update_rule_provider("reject").await.unwrap();
+
+    let rules = api.get_rules().await.unwrap();
+    assert_eq!(rules[0].size, 3);
+    assert_eq!(rules[1].proxy, "DIRECT");
+
+    let query = api.dns_query("example.com", "A").await.unwrap();
+    assert_eq!(query.question[0].name, "example.com.");
+    assert_eq!(query.answer[0].data, "1.1.1.1");
+
+    api.flush_fakeip_cache().await.unwrap();
+    api.flush_dns_cache().await.unwrap();
+    api.upgrade_core().await.unwrap();
+    api.upgrade_ui().await.unwrap();
+    api.upgrade_geo().await.unwrap();
+
+    let mut traffic = vec![];
+    api.stream("/traffic", |item: TrafficRes| traffic.push(item))
+        .await
+        .unwrap();
+    let traffic = traffic.iter().map(|t| (t.up, t.down)).collect::<Vec<_>>();
+    assert_eq!(traffic, vec![(1, 2), (3, 4), (5, 6)]);
+
+    // not found and unauthorized
+    assert!(api.get_configs().await.unwrap_err().status().is_some());
+    let api = ClashApi::new(&format!("http://{addr}"), Some("wrong")).unwrap();
+    assert!(api.version().await.unwrap_err().status().is_some());
+
+    // the core is not running
+    let api = ClashApi::new("http://127.0.0.1:1", None).unwrap();
+    assert!(matches!(
+        api.version().await,
+        Err(ClashApiError::Request(_))
+    ));
+}
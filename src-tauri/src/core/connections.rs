@@ -0,0 +1,130 @@
+use super::{
+    clash_api::{ClashApi, Connection},
+    selected,
+};
+use crate::config::Config;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// 筛选连接，所有条件都满足才匹配，字符串忽略大小写
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct ConnectionFilter {
+    /// part of the host, the sniffed host or the destination ip
+    pub host: Option<String>,
+    /// part of the process name or the process path
+    pub process: Option<String>,
+    /// part of the rule type or the rule payload, like `GEOSITE` or `google`
+    pub rule: Option<String>,
+    /// the name of the proxy or the proxy group in the chains
+    pub chain: Option<String>,
+}
+
+impl ConnectionFilter {
+    pub fn by_chain(chain: &str) -> Self {
+        Self {
+            chain: Some(chain.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.host.is_none() && self.process.is_none() && self.rule.is_none() && self.chain.is_none()
+    }
+
+    pub fn matches(&self, conn: &Connection) -> bool {
+        let contains = |pattern: &Option<String>, values: &[&str]| match pattern {
+            Some(pattern) => {
+                let pattern = pattern.to_lowercase();
+                values.iter().any(|v| v.to_lowercase().contains(&pattern))
+            }
+            None => true,
+        };
+
+        let meta = &conn.metadata;
+        contains(
+            &self.host,
+            &[&meta.host, &meta.sniff_host, &meta.destination_ip],
+        ) && contains(&self.process, &[&meta.process, &meta.process_path])
+            && contains(&self.rule, &[&conn.rule, &conn.rule_payload])
+            && self.chain.iter().all(|c| conn.chains.contains(c))
+    }
+}
+
+/// list the active connections matching the filter
+pub async fn get_connections(filter: &ConnectionFilter) -> Result<Vec<Connection>> {
+    let connections = ClashApi::current()?.get_connections().await?.connections;
+    let connections = connections.into_iter().filter(|c| filter.matches(c));
+    Ok(connections.collect())
+}
+
+/// close the connections matching the filter, return the count
+pub async fn close_connections(filter: &ConnectionFilter) -> Result<usize> {
+    let api = ClashApi::current()?;
+    let connections = api.get_connections().await?.connections;
+    if filter.is_empty() {
+        api.close_all_connections().await?;
+        return Ok(connections.len());
+    }
+
+    let mut count = 0;
+    for conn in connections.iter().filter(|c| filter.matches(c)) {
+        match api.close_connection(&conn.id).await {
+            Ok(_) => count += 1,
+            // the connection may be closed already
+            Err(err) => log::debug!(target: "app", "failed to close connection {}: {err}", conn.id),
+        }
+    }
+    Ok(count)
+}
+
+/// change the selected proxy of the group and remember it in the current profile
+/// close the connections of the group if `auto_close_connection` is enabled
+pub async fn select_proxy(group: &str, proxy: &str) -> Result<()> {
+    let api = ClashApi::current()?;
+    let now = api.get_proxy(group).await?.now;
+    api.select_proxy(group, proxy).await?;
+    selected::remember_selected(group, proxy)?;
+
+    let auto_close = Config::verge().latest().auto_close_connection;
+    if auto_close.unwrap_or(true) && now.as_deref() != Some(proxy) {
+        let count = close_connections(&ConnectionFilter::by_chain(group)).await?;
+        log::debug!(target: "app", "close {count} connections of the group {group}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_connection_filter() {
+    let mut conn = Connection {
+        id: "1".into(),
+        chains: vec!["node".into(), "Proxy".into()],
+        rule: "GeoSite".into(),
+        rule_payload: "google".into(),
+        ..Connection::default()
+    };
+    conn.metadata.host = "www.Google.com".into();
+    conn.metadata.process = "chrome.exe".into();
+    conn.metadata.process_path = "C:\\Program Files\\Google\\chrome.exe".into();
+
+    let filter =
+        |host: Option<&str>, process: Option<&str>, rule: Option<&str>, chain: Option<&str>| {
+            ConnectionFilter {
+                host: host.map(String::from),
+                process: process.map(String::from),
+                rule: rule.map(String::from),
+                chain: chain.map(String::from),
+            }
+        };
+
+    assert!(filter(None, None, None, None).matches(&conn));
+    assert!(filter(Some("google.com"), None, None, None).matches(&conn));
+    assert!(filter(None, Some("Program Files"), None, None).matches(&conn));
+    assert!(filter(None, None, Some("geosite"), Some("Proxy")).matches(&conn));
+    assert!(filter(Some("google"), Some("chrome"), Some("google"), Some("node")).matches(&conn));
+
+    assert!(!filter(Some("github"), None, None, None).matches(&conn));
+    assert!(!filter(None, Some("firefox"), None, None).matches(&conn));
+    assert!(!filter(None, None, Some("MATCH"), None).matches(&conn));
+    // the chain should be the same name
+    assert!(!filter(None, None, None, Some("prox")).matches(&conn));
+}
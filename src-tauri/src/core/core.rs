@@ -1,5 +1,14 @@
 use crate::config::*;
-use crate::core::{clash_api, handle, logger::Logger, service};
+use crate::core::{
+    binary::CoreBinary,
+    clash_api::{self, ClashApi, ClashApiError},
+    handle,
+    instance::{self, CoreInstance, InstanceStatus},
+    logger::Logger,
+    probe::{self, CoreError},
+    process, selected, service,
+    supervisor::Supervisor,
+};
 use crate::log_err;
 use crate::utils::dirs;
 use crate::utils::resolve::find_unused_port;
@@ -7,10 +16,17 @@ use anyhow::{bail, Result};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use serde_yaml::Mapping;
-use std::{sync::Arc, time::Duration};
-use sysinfo::System;
-use tauri::api::process::{Command, CommandChild, CommandEvent};
-use tokio::time::sleep;
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+use tauri::api::process::{CommandChild, CommandEvent};
+use tokio::{sync::oneshot, time::sleep};
+
+/// 重载后等待新的外部控制的时间
+const CONTROLLER_TIMEOUT: Duration = Duration::from_secs(3);
 
 #[derive(Debug)]
 pub struct CoreManager {
@@ -22,6 +38,9 @@ pub struct CoreManager {
 
     /// true if clash core needs to be restarted when it is terminated
     need_restart_core: Arc<Mutex<bool>>,
+
+    /// the core instances with their own profiles, keyed by name
+    instances: Arc<Mutex<HashMap<String, CoreInstance>>>,
 }
 
 impl CoreManager {
@@ -32,6 +51,7 @@ impl CoreManager {
             sidecar: Arc::new(Mutex::new(None)),
             use_service_mode: Arc::new(Mutex::new(false)),
             need_restart_core: Arc::new(Mutex::new(true)),
+            instances: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
@@ -55,6 +75,8 @@ impl CoreManager {
             }
             // 启动 clash
             log_err!(Self::global().run_core().await);
+            // 启动设置了自启的内核实例
+            Self::global().start_instances().await;
         });
 
         Ok(())
@@ -62,16 +84,24 @@ impl CoreManager {
 
     /// 检查订阅是否正确
     pub fn check_config(&self) -> Result<()> {
+        self.check_config_with(&CoreBinary::current()?)
+    }
+
+    /// 用指定的内核检查订阅
+    pub fn check_config_with(&self, binary: &CoreBinary) -> Result<()> {
         let config_path = Config::generate_file(ConfigType::Check)?;
-        let config_path = dirs::path_to_str(&config_path)?;
+        self.check_file(binary, &config_path, Logger::global())
+    }
 
-        let clash_core = { Config::verge().latest().clash_core.clone() };
-        let clash_core = clash_core.unwrap_or("clash".into());
+    /// 检查生成的配置文件，失败的输出写到对应的 logger
+    fn check_file(&self, binary: &CoreBinary, config_path: &Path, logger: &Logger) -> Result<()> {
+        let config_path = dirs::path_to_str(config_path)?;
 
         let app_dir = dirs::app_home_dir()?;
         let app_dir = dirs::path_to_str(&app_dir)?;
 
-        let output = Command::new_sidecar(clash_core)?
+        let output = binary
+            .command()?
             .args(["-t", "-d", app_dir, "-f", config_path])
             .output()?;
 
@@ -81,7 +111,7 @@ impl CoreManager {
                 true => error,
                 false => output.stdout.clone(),
             };
-            Logger::global().set_log(output.stdout);
+            logger.set_log(output.stdout);
             bail!("{error}");
         }
 
@@ -99,15 +129,16 @@ impl CoreManager {
 
         if self.sidecar.lock().is_some() {
             *self.need_restart_core.lock() = false;
-            self.sidecar.lock().take();
             // 关闭 tun 模式
             log::debug!(target: "app", "disable tun mode");
             let _ = clash_api::patch_configs(&disable).await;
+            self.kill_sidecar()?;
         }
 
         if *self.use_service_mode.lock() {
             log::debug!(target: "app", "stop the core by service");
             log_err!(service::stop_core_by_service().await);
+            process::CoreProcess::remove(&dirs::core_pid_path()?);
         }
 
         // 服务模式
@@ -115,20 +146,31 @@ impl CoreManager {
         let enable = enable.unwrap_or(false);
         *self.use_service_mode.lock() = enable;
 
-        let mut system = System::new();
-        system.refresh_all();
-        let procs = system.processes_by_name("verge-mihomo".as_ref());
-        for proc in procs {
-            log::debug!(target: "app", "kill all clash process");
-            proc.kill();
-        }
+        self.kill_stale_core().await?;
 
         if enable {
             // 服务模式启动失败就直接运行 sidecar
             log::debug!(target: "app", "try to run core in service mode");
             let res = service::run_core_by_service(&config_path).await;
             match res {
-                Ok(_) => return Ok(()),
+                Ok(_) => {
+                    if let Ok(Some(pid)) = service::core_pid_by_service().await {
+                        process::track(&dirs::core_pid_path()?, pid, true);
+                    }
+                    // 内核由服务管理，控制器没有就绪只提示，不算启动失败
+                    let api = ClashApi::latest()?;
+                    match probe::wait_ready(&api, probe::READY_TIMEOUT, None).await {
+                        Ok(_) => {
+                            Supervisor::global().on_started();
+                            log_err!(selected::restore_selected(&api).await);
+                        }
+                        Err(err) => {
+                            log::error!(target: "app", "the core run by service is not ready: {err}");
+                            handle::Handle::notice_message("set_config::error", format!("{err}"));
+                        }
+                    }
+                    return Ok(());
+                }
                 Err(err) => {
                     // 修改这个值，免得stop出错
                     *self.use_service_mode.lock() = false;
@@ -150,19 +192,26 @@ impl CoreManager {
 
         let app_dir = dirs::app_home_dir()?;
         let app_dir = dirs::path_to_str(&app_dir)?;
-        let clash_core = { Config::verge().latest().clash_core.clone() };
-        let clash_core = clash_core.unwrap_or("verge-mihomo".into());
+        let binary = CoreBinary::current()?;
 
         let config_path = dirs::path_to_str(&config_path)?;
         let args = vec!["-d", app_dir, "-f", config_path];
 
-        let cmd = Command::new_sidecar(clash_core)?;
+        // 只保留这次启动的输出，用于判断启动失败的原因
+        Logger::global().clear_log();
+        let cmd = binary.command()?;
         let (mut rx, cmd_child) = cmd.args(args).spawn()?;
+        let pid = cmd_child.pid();
         {
             let mut sidecar = self.sidecar.lock();
             *sidecar = Some(cmd_child);
         }
+        process::track(&dirs::core_pid_path()?, pid, false);
+        // 旧的 sidecar 已经替换，新的 sidecar 退出时需要恢复
+        *self.need_restart_core.lock() = true;
+        let (exit_tx, exit_rx) = oneshot::channel();
         tauri::async_runtime::spawn(async move {
+            let mut exit_tx = Some(exit_tx);
             while let Some(event) = rx.recv().await {
                 match event {
                     CommandEvent::Stdout(line) => {
@@ -177,9 +226,24 @@ impl CoreManager {
                         log::error!(target: "app", "[mihomo]: {err}");
                         Logger::global().set_log(err);
                     }
-                    CommandEvent::Terminated(_) => {
+                    CommandEvent::Terminated(payload) => {
                         log::info!(target: "app", "mihomo core terminated");
-                        let _ = CoreManager::global().recover_core();
+                        let reason = format!(
+                            "exited with code {:?}, signal {:?}",
+                            payload.code, payload.signal
+                        );
+                        // 启动过程中退出，由 run_core 返回错误
+                        if let Some(tx) = exit_tx.take() {
+                            if tx.send(reason.clone()).is_ok() {
+                                break;
+                            }
+                        }
+                        let manager = CoreManager::global();
+                        // 被替换掉的旧 sidecar 不需要恢复
+                        let current = manager.sidecar.lock().as_ref().map(|c| c.pid());
+                        if current == Some(pid) {
+                            let _ = manager.recover_core(reason);
+                        }
                         break;
                     }
                     _ => {}
@@ -187,11 +251,27 @@ impl CoreManager {
             }
         });
 
+        // 等待外部控制可用，内核用的是最新的配置
+        let api = ClashApi::latest()?;
+        if let Err(err) = probe::wait_ready(&api, probe::READY_TIMEOUT, Some(exit_rx)).await {
+            if !matches!(err, CoreError::Unreachable(_)) {
+                let mut sidecar = self.sidecar.lock();
+                if sidecar.as_ref().is_some_and(|c| c.pid() == pid) {
+                    sidecar.take();
+                }
+            }
+            return Err(err.into());
+        }
+
+        Supervisor::global().on_started();
+        // 恢复当前订阅的节点选择
+        log_err!(selected::restore_selected(&api).await);
         Ok(())
     }
 
     /// 重启内核
-    pub fn recover_core(&'static self) -> Result<()> {
+    /// 按照 supervisor 的退避时间重启，频繁崩溃则不再重启
+    pub fn recover_core(&'static self, reason: String) -> Result<()> {
         // 服务模式 / 切换内核 不进行恢复
         if *self.use_service_mode.lock() || !*self.need_restart_core.lock() {
             return Ok(());
@@ -199,14 +279,18 @@ impl CoreManager {
         // 清空原来的 sidecar 值
         let _ = self.sidecar.lock().take();
 
+        let Some(delay) = Supervisor::global().on_crash(&reason) else {
+            return Ok(());
+        };
+
         tauri::async_runtime::spawn(async move {
-            if self.sidecar.lock().is_none() {
+            sleep(delay).await;
+            // 等待期间可能已经手动重启或者停止了
+            if self.sidecar.lock().is_none() && *self.need_restart_core.lock() {
                 log::info!(target: "app", "recover clash core");
-                // 重新启动app
                 if let Err(err) = self.run_core().await {
                     log::error!(target: "app", "failed to recover clash core");
-                    log::error!(target: "app", "{err}");
-                    let _ = self.recover_core();
+                    let _ = self.recover_core(format!("failed to start: {err}"));
                 }
             }
         });
@@ -216,7 +300,9 @@ impl CoreManager {
 
     /// 停止核心运行
     pub fn stop_core(&self) -> Result<()> {
+        self.stop_instances();
         *self.need_restart_core.lock() = false;
+        Supervisor::global().on_stopped();
         // 关闭tun模式
         tauri::async_runtime::block_on(async move {
             let mut disable = Mapping::new();
@@ -232,36 +318,197 @@ impl CoreManager {
             tauri::async_runtime::block_on(async move {
                 log_err!(service::stop_core_by_service().await);
             });
+            process::CoreProcess::remove(&dirs::core_pid_path()?);
             return Ok(());
         }
 
-        {
-            let mut sidecar = self.sidecar.lock();
-            let _ = sidecar.take();
+        self.kill_sidecar()
+    }
+
+    /// 只结束 app 启动的 sidecar 和它的子进程，不按名字结束其他的内核
+    fn kill_sidecar(&self) -> Result<()> {
+        let sidecar = self.sidecar.lock().take();
+        if let Some(sidecar) = sidecar {
+            process::kill_tree(sidecar.pid());
         }
+        process::CoreProcess::remove(&dirs::core_pid_path()?);
+        Ok(())
+    }
 
-        let mut system = System::new();
-        system.refresh_all();
-        let procs = system.processes_by_name("verge-mihomo".as_ref());
-        for proc in procs {
-            log::debug!(target: "app", "kill all clash process");
-            proc.kill();
+    /// app 上次异常退出时留下的内核
+    async fn kill_stale_core(&self) -> Result<()> {
+        let pid_file = dirs::core_pid_path()?;
+        let Some(stale) = process::stale_core(&pid_file) else {
+            return Ok(());
+        };
+        log::warn!(target: "app", "stop the core left by the last run, pid {}", stale.pid);
+        if stale.service {
+            log_err!(service::stop_core_by_service().await);
+        } else {
+            process::kill_tree(stale.pid);
         }
+        process::CoreProcess::remove(&pid_file);
         Ok(())
     }
 
+    /// 启动内核实例，实例退出后不会自动重启
+    pub async fn start_instance(&self, name: &str) -> Result<()> {
+        let target = instance::find(name)?;
+        let (main, others) = {
+            let main = Config::clash().latest().get_client_info();
+            let others = Config::verge().latest().core_instances.clone();
+            (main, others.unwrap_or_default())
+        };
+        instance::validate(&target, &main, &others)?;
+
+        self.stop_instance(name)?;
+        // 实例用自己的目录，缓存和下载的 provider 不和主内核共用
+        let home_dir = instance::init_home(name)?;
+        let pid_file = instance::pid_path(name)?;
+        if let Some(stale) = process::stale_core(&pid_file) {
+            log::warn!(target: "app", "stop the instance \"{name}\" left by the last run");
+            process::kill_tree(stale.pid);
+            process::CoreProcess::remove(&pid_file);
+        }
+
+        let logger = Arc::new(Logger::default());
+        let binary = CoreBinary::current()?;
+        let config_path = Config::generate_file(ConfigType::Instance(name.into()))?;
+        self.check_file(&binary, &config_path, &logger)?;
+
+        let home_dir = dirs::path_to_str(&home_dir)?;
+        let config_path = dirs::path_to_str(&config_path)?;
+        let (mut rx, cmd_child) = binary
+            .command()?
+            .args(["-d", home_dir, "-f", config_path])
+            .spawn()?;
+        let pid = cmd_child.pid();
+        process::track(&pid_file, pid, false);
+        self.instances.lock().insert(
+            name.into(),
+            CoreInstance {
+                child: Some(cmd_child),
+                logger: logger.clone(),
+            },
+        );
+
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let instance_name = name.to_string();
+        let instance_logger = logger.clone();
+        tauri::async_runtime::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Stdout(line) => instance_logger.set_log(line),
+                    CommandEvent::Stderr(err) | CommandEvent::Error(err) => {
+                        log::error!(target: "app", "[{instance_name}]: {err}");
+                        instance_logger.set_log(err);
+                    }
+                    CommandEvent::Terminated(payload) => {
+                        let reason = format!(
+                            "exited with code {:?}, signal {:?}",
+                            payload.code, payload.signal
+                        );
+                        log::info!(target: "app", "core instance \"{instance_name}\" {reason}");
+                        let mut instances = CoreManager::global().instances.lock();
+                        if let Some(instance) = instances.get_mut(&instance_name) {
+                            if instance.child.as_ref().is_some_and(|c| c.pid() == pid) {
+                                instance.child = None;
+                                process::CoreProcess::remove(&pid_file);
+                            }
+                        }
+                        let _ = exit_tx.send(reason);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        let api = instance::api(&target)?;
+        if let Err(err) = probe::wait_ready(&api, probe::READY_TIMEOUT, Some(exit_rx)).await {
+            log_err!(self.stop_instance(name));
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    /// 停止内核实例，保留它的日志
+    pub fn stop_instance(&self, name: &str) -> Result<()> {
+        // the name is checked before it's used in the path
+        let pid_file = instance::pid_path(name)?;
+        let child = {
+            let mut instances = self.instances.lock();
+            instances.get_mut(name).and_then(|i| i.child.take())
+        };
+        if let Some(child) = child {
+            process::kill_tree(child.pid());
+        }
+        process::CoreProcess::remove(&pid_file);
+        Ok(())
+    }
+
+    /// 停止所有的内核实例
+    pub fn stop_instances(&self) {
+        let names = self.instances.lock().keys().cloned().collect::<Vec<_>>();
+        for name in names {
+            log_err!(self.stop_instance(&name));
+        }
+    }
+
+    /// 启动设置了 autostart 的内核实例
+    pub async fn start_instances(&self) {
+        let instances = { Config::verge().latest().core_instances.clone() };
+        for item in instances.unwrap_or_default() {
+            if item.autostart.unwrap_or(false) {
+                if let Err(err) = self.start_instance(&item.name).await {
+                    log::error!(target: "app", "failed to start the core instance \"{}\": {err}", item.name);
+                }
+            }
+        }
+    }
+
+    /// 配置里的实例和它们的运行状态
+    pub fn instance_status(&self) -> Vec<InstanceStatus> {
+        let instances = { Config::verge().latest().core_instances.clone() };
+        let running = self.instances.lock();
+        instances
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| {
+                let pid = running
+                    .get(&item.name)
+                    .and_then(|i| i.child.as_ref())
+                    .map(|c| c.pid());
+                InstanceStatus {
+                    running: pid.is_some(),
+                    pid,
+                    name: item.name,
+                    profile: item.profile,
+                    mixed_port: item.mixed_port,
+                    external_controller: item.external_controller,
+                }
+            })
+            .collect()
+    }
+
+    /// 内核实例最近的输出
+    pub fn instance_logs(&self, name: &str) -> Result<VecDeque<String>> {
+        match self.instances.lock().get(name) {
+            Some(instance) => Ok(instance.logger.get_log()),
+            None => bail!("the core instance \"{name}\" has not been started"),
+        }
+    }
+
     /// 切换核心
     pub async fn change_core(&self, clash_core: Option<String>) -> Result<()> {
         *self.need_restart_core.lock() = false;
 
         let clash_core = clash_core.ok_or(anyhow::anyhow!("clash core is null"))?;
-        const CLASH_CORES: [&str; 2] = ["verge-mihomo", "verge-mihomo-alpha"];
-        if !CLASH_CORES.contains(&clash_core.as_str()) {
-            bail!("invalid clash core name \"{clash_core}\"");
-        }
+        CoreBinary::resolve(&clash_core)?;
 
         log::debug!(target: "app", "change core to `{clash_core}`");
         Config::verge().draft().clash_core = Some(clash_core);
+        CoreBinary::refresh_current_version();
         // 更新订阅
         Config::generate()?;
         self.check_config()?;
@@ -285,6 +532,31 @@ impl CoreManager {
         }
     }
 
+    /// 修改了外部控制，用旧的外部控制重载配置，再用新的地址和密钥连接
+    /// 内核没有重新监听的话才重启
+    pub async fn reload_controller(&self) -> Result<()> {
+        Config::generate()?;
+        self.check_config()?;
+        let path = Config::generate_file(ConfigType::Run)?;
+        let path = dirs::path_to_str(&path)?;
+
+        let api = ClashApi::latest()?;
+        let reloaded = match ClashApi::current()?.put_configs(path).await {
+            Ok(_) => probe::wait_ready(&api, CONTROLLER_TIMEOUT, None)
+                .await
+                .map_err(anyhow::Error::from),
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = reloaded {
+            log::warn!(target: "app", "the controller is not changed by reloading, restart the core: {err}");
+            return self.run_core().await;
+        }
+
+        // 重载配置会重置分组的选择
+        log_err!(selected::restore_selected(&api).await);
+        Ok(())
+    }
+
     /// 更新proxies那些
     /// 如果涉及端口和外部控制则需要重启
     pub async fn update_config(&self) -> Result<()> {
@@ -300,21 +572,20 @@ impl CoreManager {
         let path = Config::generate_file(ConfigType::Run)?;
         let path = dirs::path_to_str(&path)?;
 
-        // 发送请求 发送5次
-        for i in 0..5 {
-            match clash_api::put_configs(path).await {
-                Ok(_) => break,
-                Err(err) => {
-                    if i < 4 {
-                        log::info!(target: "app", "{err}");
-                    } else {
-                        bail!(err);
-                    }
-                }
+        // 等待外部控制可用后再重载配置
+        let api = ClashApi::current()?;
+        probe::wait_ready(&api, probe::READY_TIMEOUT, None).await?;
+        match api.put_configs(path).await {
+            Ok(_) => {}
+            Err(ClashApiError::Status { message, .. }) => {
+                let err = probe::classify(&[message.clone()]);
+                bail!(err.unwrap_or(CoreError::BadConfig(message)));
             }
-            sleep(Duration::from_millis(250)).await;
+            Err(err) => bail!(CoreError::Unreachable(err.to_string())),
         }
 
+        // 重载配置会重置分组的选择
+        log_err!(selected::restore_selected(&api).await);
         Ok(())
     }
 }
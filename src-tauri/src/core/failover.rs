@@ -0,0 +1,248 @@
+use super::{
+    clash_api::{ClashApi, ClashApiError},
+    connections, handle,
+    latency::{LatencyStats, LatencyTester},
+};
+use crate::{config::Config, log_err, utils::dirs::APP_ID};
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tauri::api::notification::Notification;
+
+#[derive(Debug, Default)]
+struct GroupState {
+    /// the proxy being watched
+    proxy: String,
+    /// consecutive failed checks
+    failures: u32,
+    last_switch: Option<Instant>,
+}
+
+/// the consecutive failures and the last switch of each group
+#[derive(Debug, Default)]
+pub struct FailoverState {
+    groups: HashMap<String, GroupState>,
+}
+
+impl FailoverState {
+    /// record the check of the selected proxy, return true if the group should switch
+    pub fn check(
+        &mut self,
+        group: &str,
+        proxy: &str,
+        ok: bool,
+        now: Instant,
+        max_failures: u32,
+        cooldown: Duration,
+    ) -> bool {
+        let state = self.groups.entry(group.into()).or_default();
+        // 用户手动切换了节点，重新计数
+        if state.proxy != proxy {
+            state.proxy = proxy.into();
+            state.failures = 0;
+        }
+        if ok {
+            state.failures = 0;
+            return false;
+        }
+
+        state.failures += 1;
+        if state.failures < max_failures.max(1) {
+            return false;
+        }
+        // 避免来回切换
+        match state.last_switch {
+            Some(last) => now.duration_since(last) >= cooldown,
+            None => true,
+        }
+    }
+
+    /// reset the failures and start the cooldown
+    pub fn switched(&mut self, group: &str, proxy: &str, now: Instant) {
+        let state = self.groups.entry(group.into()).or_default();
+        state.proxy = proxy.into();
+        state.failures = 0;
+        state.last_switch = Some(now);
+    }
+
+    /// forget the groups that are no longer watched
+    pub fn retain(&mut self, groups: &[String]) {
+        self.groups.retain(|name, _| groups.contains(name));
+    }
+}
+
+/// the best alive proxy in the ranking except the current one
+pub fn pick_alternative(ranking: &[LatencyStats], current: &str) -> Option<String> {
+    let alive = ranking.iter().filter(|s| s.last_delay.is_some());
+    alive
+        .map(|s| &s.name)
+        .find(|name| *name != current)
+        .cloned()
+}
+
+/// 自动故障转移
+/// 定时检测开启的 selector 分组的当前节点，连续失败后切换到组内最好的节点
+pub struct Failover {
+    state: Mutex<FailoverState>,
+    started: OnceCell<()>,
+}
+
+impl Failover {
+    pub fn global() -> &'static Failover {
+        static FAILOVER: OnceCell<Failover> = OnceCell::new();
+
+        FAILOVER.get_or_init(|| Failover {
+            state: Mutex::new(FailoverState::default()),
+            started: OnceCell::new(),
+        })
+    }
+
+    /// start the watchdog, only once
+    pub fn init(&'static self) {
+        if self.started.set(()).is_err() {
+            return;
+        }
+
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let interval = Config::verge().latest().failover_check_interval;
+                let interval = interval.unwrap_or(60).max(5);
+                tokio::time::sleep(Duration::from_secs(interval)).await;
+                log_err!(self.check_all().await);
+            }
+        });
+    }
+
+    async fn check_all(&self) -> Result<()> {
+        let (groups, max_failures, cooldown, test_url, timeout) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                verge.failover_groups.clone().unwrap_or_default(),
+                verge.failover_max_failures.unwrap_or(3),
+                Duration::from_secs(verge.failover_cooldown.unwrap_or(300)),
+                verge.default_latency_test.clone().filter(|u| !u.is_empty()),
+                verge.default_latency_timeout.unwrap_or(5000),
+            )
+        };
+        self.state.lock().retain(&groups);
+        if groups.is_empty() {
+            return Ok(());
+        }
+
+        let api = ClashApi::current()?;
+        for group in groups.iter() {
+            let proxy = match api.get_proxy(group).await {
+                Ok(item) if item.ptype == "Selector" => item.now.unwrap_or_default(),
+                Ok(_) => continue,
+                // the group was removed from the profile
+                Err(ClashApiError::Status { .. }) => continue,
+                // the core is not running
+                Err(err) => return Err(err.into()),
+            };
+
+            let ok = match api
+                .get_proxy_delay(&proxy, test_url.as_deref(), timeout)
+                .await
+            {
+                Ok(_) => true,
+                Err(ClashApiError::Status { .. }) => false,
+                Err(err) => return Err(err.into()),
+            };
+
+            let now = Instant::now();
+            let should_switch = {
+                let mut state = self.state.lock();
+                state.check(group, &proxy, ok, now, max_failures, cooldown)
+            };
+            if !should_switch {
+                continue;
+            }
+            if let Some(new) = self.switch(group, &proxy).await {
+                self.state.lock().switched(group, &new, Instant::now());
+            }
+        }
+        Ok(())
+    }
+
+    /// test the group and select the best proxy, return the newly selected one
+    async fn switch(&self, group: &str, current: &str) -> Option<String> {
+        let tester = LatencyTester::global();
+        log_err!(tester.test_all(Some(group)).await);
+
+        let ranking = tester.ranking(Some(group)).await.unwrap_or_default();
+        let Some(proxy) = pick_alternative(&ranking, current) else {
+            log::warn!(target: "app", "[failover] {current} of {group} is down, but no proxy is available");
+            return None;
+        };
+
+        match connections::select_proxy(group, &proxy).await {
+            Ok(_) => {
+                log::warn!(target: "app", "[failover] switch {group} from {current} to {proxy}");
+                handle::Handle::emit("verge://failover", (group, current, &proxy));
+                handle::Handle::refresh_clash();
+                let _ = Notification::new(APP_ID)
+                    .title("Clash Verge")
+                    .body(format!("{group}: {current} -> {proxy}"))
+                    .show();
+                Some(proxy)
+            }
+            Err(err) => {
+                log::error!(target: "app", "[failover] failed to switch {group}: {err}");
+                None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_failover_state() {
+    let cooldown = Duration::from_secs(300);
+    let start = Instant::now();
+    let at = |secs: u64| start + Duration::from_secs(secs);
+    let mut state = FailoverState::default();
+
+    // switch after 3 consecutive failures
+    assert!(!state.check("g", "a", false, at(0), 3, cooldown));
+    assert!(!state.check("g", "a", false, at(1), 3, cooldown));
+    assert!(state.check("g", "a", false, at(2), 3, cooldown));
+    state.switched("g", "b", at(2));
+    assert_eq!(state.groups["g"].proxy, "b");
+
+    // a success resets the count
+    assert!(!state.check("g", "b", false, at(3), 3, cooldown));
+    assert!(!state.check("g", "b", true, at(4), 3, cooldown));
+    assert!(!state.check("g", "b", false, at(5), 3, cooldown));
+    assert!(!state.check("g", "b", false, at(6), 3, cooldown));
+    // in the cooldown
+    assert!(!state.check("g", "b", false, at(7), 3, cooldown));
+    assert!(state.check("g", "b", false, at(400), 3, cooldown));
+
+    // selected another proxy manually
+    assert!(!state.check("g", "c", false, at(401), 3, cooldown));
+
+    state.retain(&[]);
+    assert!(state.groups.is_empty());
+}
+
+#[test]
+fn test_pick_alternative() {
+    let stats = |name: &str, last_delay: Option<u64>| LatencyStats {
+        name: name.into(),
+        last_delay,
+        ..LatencyStats::default()
+    };
+    let ranking = vec![
+        stats("a", Some(100)),
+        stats("b", None),
+        stats("c", Some(300)),
+    ];
+
+    assert_eq!(pick_alternative(&ranking, "b").as_deref(), Some("a"));
+    assert_eq!(pick_alternative(&ranking, "a").as_deref(), Some("c"));
+    assert_eq!(pick_alternative(&ranking[1..2], "a"), None);
+}
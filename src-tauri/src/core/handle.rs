@@ -1,8 +1,9 @@
 use super::tray::Tray;
 use crate::log_err;
-use anyhow::{bail, Ok, Result};
+use anyhow::{Ok, Result};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
+use serde::Serialize;
 use std::sync::Arc;
 use tauri::{AppHandle, Manager, Window};
 
@@ -50,37 +51,43 @@ impl Handle {
         }
     }
 
+    /// emit to all the windows and the rust listeners, works without the window
+    pub fn emit<S: Serialize + Clone>(event: &str, payload: S) {
+        let app_handle = Self::global().app_handle.lock().clone();
+        if let Some(app_handle) = app_handle {
+            log_err!(app_handle.emit_all(event, payload));
+        }
+    }
+
     pub fn notice_message<S: Into<String>, M: Into<String>>(status: S, msg: M) {
         if let Some(window) = Self::global().get_window() {
             log_err!(window.emit("verge://notice-message", (status.into(), msg.into())));
         }
     }
 
+    /// the headless mode has no tray, nothing to update
     pub fn update_systray() -> Result<()> {
         let app_handle = Self::global().app_handle.lock();
-        if app_handle.is_none() {
-            bail!("update_systray unhandled error");
+        if let Some(app_handle) = app_handle.as_ref() {
+            Tray::update_systray(app_handle)?;
         }
-        Tray::update_systray(app_handle.as_ref().unwrap())?;
         Ok(())
     }
 
     /// update the system tray state
     pub fn update_systray_part() -> Result<()> {
         let app_handle = Self::global().app_handle.lock();
-        if app_handle.is_none() {
-            bail!("update_systray unhandled error");
+        if let Some(app_handle) = app_handle.as_ref() {
+            Tray::update_part(app_handle)?;
         }
-        Tray::update_part(app_handle.as_ref().unwrap())?;
         Ok(())
     }
 
     pub fn set_tray_visible(visible: bool) -> Result<()> {
         let app_handle = Self::global().app_handle.lock();
-        if app_handle.is_none() {
-            bail!("set_tray_visible unhandled error");
+        if let Some(app_handle) = app_handle.as_ref() {
+            Tray::set_tray_visible(app_handle, visible)?;
         }
-        Tray::set_tray_visible(app_handle.as_ref().unwrap(), visible)?;
         Ok(())
     }
 }
@@ -0,0 +1,259 @@
+use super::{clash_api::ClashApi, logger::Logger};
+use crate::{
+    config::{ClashInfo, Config, IVergeInstance},
+    utils::dirs,
+};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::{fs, path::PathBuf, sync::Arc};
+use tauri::api::process::CommandChild;
+
+/// the ports disabled in the instance, only `mixed-port` is used
+const DISABLED_PORTS: [&str; 4] = ["port", "socks-port", "redir-port", "tproxy-port"];
+/// the other listeners of the profile, they would conflict with the main core
+const DISABLED_LISTENERS: [&str; 8] = [
+    "listeners",
+    "tunnels",
+    "tuic-server",
+    "ss-config",
+    "vmess-config",
+    "external-controller-tls",
+    "external-controller-unix",
+    "external-controller-pipe",
+];
+/// copied from the app home, the instance would download them otherwise
+const GEO_FILES: [&str; 4] = ["Country.mmdb", "geoip.dat", "geosite.dat", "ASN.mmdb"];
+
+/// a core instance started besides the main core
+#[derive(Debug)]
+pub struct CoreInstance {
+    /// none if it exited
+    pub child: Option<CommandChild>,
+    pub logger: Arc<Logger>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstanceStatus {
+    pub name: String,
+    pub profile: String,
+    pub running: bool,
+    pub pid: Option<u32>,
+    pub mixed_port: u16,
+    pub external_controller: String,
+}
+
+pub fn find(name: &str) -> Result<IVergeInstance> {
+    let instances = { Config::verge().latest().core_instances.clone() };
+    match instances
+        .unwrap_or_default()
+        .into_iter()
+        .find(|i| i.name == name)
+    {
+        Some(instance) => Ok(instance),
+        None => bail!("the core instance \"{name}\" is not found"),
+    }
+}
+
+/// the name is used as the directory name
+fn check_name(name: &str) -> Result<()> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if name.is_empty() || !name.chars().all(valid) {
+        bail!("invalid instance name \"{name}\"");
+    }
+    Ok(())
+}
+
+/// `instances/<name>/`, the home of the instance
+/// the cache and the downloaded providers are not shared with the main core
+pub fn home_dir(name: &str) -> Result<PathBuf> {
+    check_name(name)?;
+    Ok(dirs::instances_dir()?.join(name))
+}
+
+/// create the home and copy the geo files if missing
+pub fn init_home(name: &str) -> Result<PathBuf> {
+    let home = home_dir(name)?;
+    fs::create_dir_all(&home)?;
+
+    let app_dir = dirs::app_home_dir()?;
+    for file in GEO_FILES {
+        let (src, dest) = (app_dir.join(file), home.join(file));
+        if src.exists() && !dest.exists() {
+            if let Err(err) = fs::copy(&src, &dest) {
+                log::warn!(target: "app", "failed to copy '{file}' to the instance \"{name}\": {err}");
+            }
+        }
+    }
+    Ok(home)
+}
+
+pub fn config_path(name: &str) -> Result<PathBuf> {
+    Ok(home_dir(name)?.join("config.yaml"))
+}
+
+pub fn pid_path(name: &str) -> Result<PathBuf> {
+    Ok(home_dir(name)?.join("core.pid"))
+}
+
+pub fn api(instance: &IVergeInstance) -> Result<ClashApi> {
+    let server = format!("http://{}", instance.external_controller);
+    ClashApi::new(&server, instance.secret.as_deref())
+}
+
+/// the instance must not share the name, the ports or the controller
+/// with the main core and the other instances
+pub fn validate(
+    instance: &IVergeInstance,
+    main: &ClashInfo,
+    others: &[IVergeInstance],
+) -> Result<()> {
+    let name = instance.name.as_str();
+    check_name(name)?;
+    if instance.profile.is_empty() {
+        bail!("the profile of \"{name}\" is not set");
+    }
+    if instance.mixed_port == 0 {
+        bail!("the mixed port of \"{name}\" is not set");
+    }
+
+    let main_ports = [main.mixed_port, main.port, main.socks_port];
+    if main_ports.contains(&instance.mixed_port) {
+        bail!("the port {} is used by the main core", instance.mixed_port);
+    }
+    if same_address(&instance.external_controller, &main.server) {
+        bail!("the controller {} is used by the main core", main.server);
+    }
+    for other in others.iter().filter(|o| o.name != instance.name) {
+        if other.mixed_port == instance.mixed_port {
+            bail!(
+                "the port {} is used by \"{}\"",
+                other.mixed_port,
+                other.name
+            );
+        }
+        if same_address(&other.external_controller, &instance.external_controller) {
+            bail!(
+                "the controller {} is used by \"{}\"",
+                other.external_controller,
+                other.name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// compare the ports, `0.0.0.0:9090` conflicts with `127.0.0.1:9090`
+fn same_address(a: &str, b: &str) -> bool {
+    let port = |addr: &str| addr.rsplit_once(':').map(|(_, port)| port.to_string());
+    match (port(a), port(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// override the ports and the controller of the generated config
+/// the other listeners are removed, tun is left to the main core
+pub fn use_instance(mut config: Mapping, instance: &IVergeInstance) -> Mapping {
+    config.insert("mixed-port".into(), instance.mixed_port.into());
+    for key in DISABLED_PORTS.iter().chain(DISABLED_LISTENERS.iter()) {
+        config.remove(key);
+    }
+    if let Some(dns) = config.get_mut("dns").and_then(Value::as_mapping_mut) {
+        dns.remove("listen");
+    }
+    config.insert(
+        "external-controller".into(),
+        instance.external_controller.clone().into(),
+    );
+    match instance.secret.as_ref() {
+        Some(secret) => config.insert("secret".into(), secret.clone().into()),
+        None => config.remove("secret"),
+    };
+    if let Some(allow_lan) = instance.allow_lan {
+        config.insert("allow-lan".into(), allow_lan.into());
+    }
+
+    let mut tun = Mapping::new();
+    tun.insert("enable".into(), false.into());
+    config.insert("tun".into(), Value::from(tun));
+    config
+}
+
+#[test]
+fn test_instance() {
+    let instance = IVergeInstance {
+        name: "lan".into(),
+        profile: "R1".into(),
+        mixed_port: 7899,
+        external_controller: "0.0.0.0:9098".into(),
+        secret: Some("abc".into()),
+        allow_lan: Some(true),
+        autostart: None,
+    };
+    let main = ClashInfo {
+        mixed_port: 7897,
+        port: 0,
+        socks_port: 0,
+        server: "127.0.0.1:9097".into(),
+        ..ClashInfo::default()
+    };
+    assert!(validate(&instance, &main, &[instance.clone()]).is_ok());
+
+    let bad_name = IVergeInstance {
+        name: "../lan".into(),
+        ..instance.clone()
+    };
+    assert!(validate(&bad_name, &main, &[]).is_err());
+
+    let same_port = IVergeInstance {
+        mixed_port: 7897,
+        ..instance.clone()
+    };
+    assert!(validate(&same_port, &main, &[]).is_err());
+
+    let same_controller = IVergeInstance {
+        external_controller: "127.0.0.1:9097".into(),
+        ..instance.clone()
+    };
+    assert!(validate(&same_controller, &main, &[]).is_err());
+
+    let other = IVergeInstance {
+        name: "other".into(),
+        external_controller: "127.0.0.1:9099".into(),
+        ..instance.clone()
+    };
+    assert!(validate(&instance, &main, &[other]).is_err());
+
+    let config = serde_yaml::from_str::<Mapping>(
+        "mixed-port: 7897\nsocks-port: 7898\nsecret: main\nexternal-controller: 127.0.0.1:9097\ntun:\n  enable: true\n",
+    )
+    .unwrap();
+    let mut config = use_instance(config, &instance);
+    assert_eq!(config.get("mixed-port"), Some(&Value::from(7899)));
+    assert_eq!(config.get("socks-port"), None);
+    assert_eq!(config.get("secret"), Some(&Value::from("abc")));
+    assert_eq!(
+        config.get("external-controller"),
+        Some(&Value::from("0.0.0.0:9098"))
+    );
+    assert_eq!(config.get("allow-lan"), Some(&Value::from(true)));
+    assert_eq!(config["tun"]["enable"], Value::from(false));
+
+    let listeners = serde_yaml::from_str::<Mapping>(
+        "dns:\n  enable: true\n  listen: 0.0.0.0:1053\nlisteners:\n  - name: in\n    type: socks\n    port: 7901\ntuic-server:\n  enable: true\nexternal-controller-tls: 0.0.0.0:9443\n",
+    )
+    .unwrap();
+    config.extend(listeners);
+    let config = use_instance(config, &instance);
+    assert_eq!(config["dns"].get("listen"), None);
+    assert_eq!(config["dns"]["enable"], Value::from(true));
+    assert_eq!(config.get("listeners"), None);
+    assert_eq!(config.get("tuic-server"), None);
+    assert_eq!(config.get("external-controller-tls"), None);
+
+    // no path is built for an invalid name
+    assert!(check_name("lan").is_ok());
+    assert!(home_dir("../lan").is_err());
+    assert!(pid_path("").is_err());
+}
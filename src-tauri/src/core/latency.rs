@@ -0,0 +1,323 @@
+use super::clash_api::{ClashApi, ClashApiError, ProxyItem, ProxyProvider};
+use crate::config::Config;
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+/// 每个节点保留最近的测试记录
+const HISTORY_LEN: usize = 100;
+/// 同时测试的节点数
+const CONCURRENCY: usize = 8;
+/// 内置的出站不需要测试
+const BUILTIN_TYPES: [&str; 5] = ["Direct", "Reject", "RejectDrop", "Pass", "Compatible"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LatencyRecord {
+    /// timestamp in milliseconds
+    pub time: i64,
+    /// none if the test failed or timed out
+    pub delay: Option<u64>,
+}
+
+/// the stability of a proxy computed from the history
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct LatencyStats {
+    pub name: String,
+    pub tested: usize,
+    pub success: usize,
+    /// 0.0 ~ 1.0
+    pub success_rate: f64,
+    pub avg_delay: Option<u64>,
+    /// the average difference between the successive successful delays
+    pub jitter: Option<u64>,
+    pub last_delay: Option<u64>,
+    /// the time of the last test
+    pub last_time: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+pub struct LatencyHistory {
+    records: HashMap<String, VecDeque<LatencyRecord>>,
+}
+
+impl LatencyHistory {
+    pub fn push(&mut self, name: &str, record: LatencyRecord) {
+        let records = self.records.entry(name.into()).or_default();
+        if records.len() >= HISTORY_LEN {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    pub fn history(&self, name: &str) -> Vec<LatencyRecord> {
+        let records = self.records.get(name);
+        records
+            .map(|r| r.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn stats(&self, name: &str) -> Option<LatencyStats> {
+        let records = self.records.get(name)?;
+        let delays = records.iter().filter_map(|r| r.delay).collect::<Vec<_>>();
+
+        let tested = records.len();
+        let success = delays.len();
+        let avg_delay = (success > 0).then(|| delays.iter().sum::<u64>() / success as u64);
+        let jitter = (success > 1).then(|| {
+            let diff = delays.windows(2).map(|w| w[0].abs_diff(w[1]));
+            diff.sum::<u64>() / (success - 1) as u64
+        });
+        let last = records.back();
+
+        Some(LatencyStats {
+            name: name.into(),
+            tested,
+            success,
+            success_rate: success as f64 / tested.max(1) as f64,
+            avg_delay,
+            jitter,
+            last_delay: last.and_then(|r| r.delay),
+            last_time: last.map(|r| r.time),
+        })
+    }
+
+    /// sort by the success rate, then the average delay plus the jitter
+    /// all the tested proxies if `names` is none
+    pub fn ranking(&self, names: Option<&[String]>) -> Vec<LatencyStats> {
+        let mut list: Vec<_> = match names {
+            Some(names) => names.iter().filter_map(|n| self.stats(n)).collect(),
+            None => self.records.keys().filter_map(|n| self.stats(n)).collect(),
+        };
+
+        list.sort_by(|a, b| {
+            let delay = |s: &LatencyStats| match s.avg_delay {
+                Some(avg) => avg + s.jitter.unwrap_or(0),
+                None => u64::MAX,
+            };
+            b.success_rate
+                .total_cmp(&a.success_rate)
+                .then_with(|| delay(a).cmp(&delay(b)))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        list
+    }
+}
+
+/// `GET /proxies` doesn't list the proxies of the `proxy-providers`,
+/// add them to find the members of the groups with `use`
+fn with_providers(
+    mut proxies: HashMap<String, ProxyItem>,
+    providers: HashMap<String, ProxyProvider>,
+) -> HashMap<String, ProxyItem> {
+    for proxy in providers.into_values().flat_map(|p| p.proxies) {
+        proxies.entry(proxy.name.clone()).or_insert(proxy);
+    }
+    proxies
+}
+
+/// the proxies to be tested, the members of the group or all the proxies
+fn test_targets(proxies: &HashMap<String, ProxyItem>, group: Option<&str>) -> Result<Vec<String>> {
+    let is_proxy = |name: &String| {
+        proxies
+            .get(name)
+            .is_some_and(|p| p.all.is_none() && !BUILTIN_TYPES.contains(&p.ptype.as_str()))
+    };
+
+    let mut names = match group {
+        Some(group) => match proxies.get(group).and_then(|g| g.all.as_ref()) {
+            Some(all) => all.iter().filter(|n| is_proxy(n)).cloned().collect(),
+            None => bail!("the group \"{group}\" does not exist"),
+        },
+        None => proxies
+            .keys()
+            .filter(|n| is_proxy(n))
+            .cloned()
+            .collect::<Vec<_>>(),
+    };
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// 定时批量测试节点延迟，记录历史用于按稳定性排序
+pub struct LatencyTester {
+    history: Arc<Mutex<LatencyHistory>>,
+    /// avoid running the batch tests at the same time
+    running: tokio::sync::Mutex<()>,
+}
+
+impl LatencyTester {
+    pub fn global() -> &'static LatencyTester {
+        static TESTER: OnceCell<LatencyTester> = OnceCell::new();
+
+        TESTER.get_or_init(|| LatencyTester {
+            history: Arc::new(Mutex::new(LatencyHistory::default())),
+            running: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// test the proxies of the group or all the proxies, return the count
+    pub async fn test_all(&self, group: Option<&str>) -> Result<usize> {
+        let _guard = self.running.lock().await;
+
+        let api = ClashApi::current()?;
+        let proxies = with_providers(api.get_proxies().await?, api.get_proxy_providers().await?);
+        let names = test_targets(&proxies, group)?;
+        let (test_url, timeout) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            let timeout = verge.default_latency_timeout.unwrap_or(5000);
+            (verge.default_latency_test.clone(), timeout)
+        };
+        let test_url = test_url.filter(|url| !url.is_empty());
+        log::debug!(target: "app", "test the latency of {} proxies", names.len());
+
+        let semaphore = Arc::new(Semaphore::new(CONCURRENCY));
+        let mut tasks = JoinSet::new();
+        for name in names {
+            let api = api.clone();
+            let test_url = test_url.clone();
+            let semaphore = semaphore.clone();
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let delay = api
+                    .get_proxy_delay(&name, test_url.as_deref(), timeout)
+                    .await;
+                (name, delay)
+            });
+        }
+
+        let mut count = 0;
+        while let Some(res) = tasks.join_next().await {
+            let delay = match res {
+                Ok((name, Ok(delay))) => (name, Some(delay)),
+                // timeout or the proxy is unreachable
+                Ok((name, Err(ClashApiError::Status { .. }))) => (name, None),
+                // the core is not running, it's not the fault of the proxy
+                Ok((name, Err(err))) => {
+                    log::debug!(target: "app", "failed to test the latency of {name}: {err}");
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            let (name, delay) = delay;
+            let record = LatencyRecord {
+                time: chrono::Local::now().timestamp_millis(),
+                delay,
+            };
+            self.history.lock().push(&name, record);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// rank the members of the group or all the tested proxies
+    pub async fn ranking(&self, group: Option<&str>) -> Result<Vec<LatencyStats>> {
+        let names = match group {
+            Some(group) => {
+                let group = ClashApi::current()?.get_group(group).await?;
+                Some(group.all.unwrap_or_default())
+            }
+            None => None,
+        };
+        Ok(self.history.lock().ranking(names.as_deref()))
+    }
+
+    pub fn history(&self, name: &str) -> Vec<LatencyRecord> {
+        self.history.lock().history(name)
+    }
+}
+
+#[test]
+fn test_latency_history() {
+    let mut history = LatencyHistory::default();
+    let mut push = |name: &str, delays: &[Option<u64>]| {
+        for (time, delay) in delays.iter().enumerate() {
+            let time = time as i64;
+            history.push(
+                name,
+                LatencyRecord {
+                    time,
+                    delay: *delay,
+                },
+            );
+        }
+    };
+
+    push("stable", &[Some(100), Some(110), Some(100), Some(110)]);
+    push("unstable", &[Some(50), Some(150), Some(50), Some(150)]);
+    push("lossy", &[Some(20), None, Some(20), None]);
+    push("dead", &[None, None]);
+    push("rolling", &vec![Some(1); HISTORY_LEN + 10]);
+
+    let stats = history.stats("lossy").unwrap();
+    assert_eq!((stats.tested, stats.success), (4, 2));
+    assert_eq!(stats.success_rate, 0.5);
+    assert_eq!((stats.avg_delay, stats.jitter), (Some(20), Some(0)));
+    assert_eq!((stats.last_delay, stats.last_time), (None, Some(3)));
+
+    let stats = history.stats("dead").unwrap();
+    assert_eq!((stats.avg_delay, stats.jitter), (None, None));
+    assert_eq!(history.stats("rolling").unwrap().tested, HISTORY_LEN);
+    assert!(history.stats("unknown").is_none());
+
+    let names = |list: Vec<LatencyStats>| list.into_iter().map(|s| s.name).collect::<Vec<_>>();
+    assert_eq!(
+        names(history.ranking(None)),
+        vec!["rolling", "stable", "unstable", "lossy", "dead"]
+    );
+    let group = ["dead", "unstable", "stable", "unknown"].map(String::from);
+    assert_eq!(
+        names(history.ranking(Some(&group))),
+        vec!["stable", "unstable", "dead"]
+    );
+}
+
+#[test]
+fn test_latency_targets() {
+    let proxy = |ptype: &str, all: Option<&[&str]>| ProxyItem {
+        ptype: ptype.into(),
+        all: all.map(|all| all.iter().map(|s| s.to_string()).collect()),
+        ..ProxyItem::default()
+    };
+    let proxies = HashMap::from([
+        ("DIRECT".into(), proxy("Direct", None)),
+        ("REJECT".into(), proxy("Reject", None)),
+        ("a".into(), proxy("Shadowsocks", None)),
+        ("b".into(), proxy("Vmess", None)),
+        ("Auto".into(), proxy("URLTest", Some(&["b", "DIRECT"]))),
+        (
+            "GLOBAL".into(),
+            proxy("Selector", Some(&["Auto", "a", "b"])),
+        ),
+    ]);
+
+    assert_eq!(test_targets(&proxies, None).unwrap(), vec!["a", "b"]);
+    assert_eq!(test_targets(&proxies, Some("Auto")).unwrap(), vec!["b"]);
+    assert_eq!(
+        test_targets(&proxies, Some("GLOBAL")).unwrap(),
+        vec!["a", "b"]
+    );
+    assert!(test_targets(&proxies, Some("a")).is_err());
+
+    // the proxies from the providers
+    let mut proxies = proxies;
+    proxies.insert("Sub".into(), proxy("Selector", Some(&["a", "c"])));
+    let provider = ProxyProvider {
+        name: "sub".into(),
+        proxies: vec![ProxyItem {
+            name: "c".into(),
+            ..proxy("Trojan", None)
+        }],
+        ..ProxyProvider::default()
+    };
+    let proxies = with_providers(proxies, HashMap::from([("sub".into(), provider)]));
+    assert_eq!(test_targets(&proxies, Some("Sub")).unwrap(), vec!["a", "c"]);
+    assert_eq!(test_targets(&proxies, None).unwrap(), vec!["a", "b", "c"]);
+}
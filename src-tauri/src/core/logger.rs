@@ -4,6 +4,8 @@ use std::{collections::VecDeque, sync::Arc};
 
 const LOGS_QUEUE_LEN: usize = 100;
 
+/// the output of the core, the global one is for the main core
+#[derive(Debug, Default)]
 pub struct Logger {
     log_data: Arc<Mutex<VecDeque<String>>>,
 }
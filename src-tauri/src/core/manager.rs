@@ -1,10 +1,10 @@
 /// 给clash内核的tun模式授权
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 pub fn grant_permission(core: String) -> anyhow::Result<()> {
+    use crate::core::binary::CoreBinary;
     use std::process::Command;
-    use tauri::utils::platform::current_exe;
 
-    let path = current_exe()?.with_file_name(core).canonicalize()?;
+    let path = CoreBinary::resolve(&core)?.path()?.canonicalize()?;
     let path = path.display().to_string();
 
     log::debug!("grant_permission path: {path}");
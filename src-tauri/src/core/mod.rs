@@ -1,14 +1,27 @@
+pub mod binary;
 pub mod clash_api;
+pub mod connections;
 #[allow(clippy::module_inception)]
 mod core;
+pub mod failover;
 pub mod handle;
 pub mod hotkey;
+pub mod instance;
+pub mod latency;
 pub mod logger;
 pub mod manager;
+pub mod monitor;
+pub mod probe;
+pub mod process;
+pub mod reload;
+pub mod selected;
 pub mod service;
+pub mod supervisor;
 pub mod sysopt;
 pub mod timer;
+pub mod traffic;
 pub mod tray;
+pub mod updater;
 pub mod win_uwp;
 pub mod backup;
 pub mod verge_log;
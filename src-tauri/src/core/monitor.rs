@@ -0,0 +1,204 @@
+use super::{
+    clash_api::{ClashApi, Connections, LogRes, MemoryRes, TrafficRes},
+    handle,
+};
+use crate::config::Config;
+use once_cell::sync::OnceCell;
+use parking_lot::{Mutex, RwLock};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+
+/// 保留最近 60 秒的流量
+const TRAFFIC_HISTORY_LEN: usize = 60;
+const LOGS_QUEUE_LEN: usize = 100;
+/// 内核重启时流会断开，稍后重连
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// `/connections` 没有 websocket 时只返回一次快照，需要定时轮询
+const CONNECTIONS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct MonitorStats {
+    /// the current speed, bytes per second
+    pub traffic: TrafficRes,
+    pub traffic_history: VecDeque<TrafficRes>,
+    /// memory used by the core
+    pub memory: u64,
+    /// the count of the active connections
+    pub connections: usize,
+    pub upload_total: u64,
+    pub download_total: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CoreLog {
+    pub time: String,
+    pub log_type: String,
+    pub payload: String,
+}
+
+/// the events from the core streams
+#[derive(Debug, Clone)]
+pub enum MonitorEvent {
+    Traffic(TrafficRes),
+    Memory(u64),
+    Log(CoreLog),
+    Connections(Arc<Connections>),
+}
+
+/// 在后端订阅内核的 `/traffic` `/memory` `/logs`，并轮询 `/connections`
+/// 不依赖窗口，托盘、通知等都可以使用
+pub struct Monitor {
+    stats: Arc<RwLock<MonitorStats>>,
+    logs: Arc<Mutex<VecDeque<CoreLog>>>,
+    sender: broadcast::Sender<MonitorEvent>,
+    started: OnceCell<()>,
+}
+
+impl Monitor {
+    pub fn global() -> &'static Monitor {
+        static MONITOR: OnceCell<Monitor> = OnceCell::new();
+
+        MONITOR.get_or_init(|| Monitor {
+            stats: Arc::new(RwLock::new(MonitorStats::default())),
+            logs: Arc::new(Mutex::new(VecDeque::with_capacity(LOGS_QUEUE_LEN + 10))),
+            sender: broadcast::channel(64).0,
+            started: OnceCell::new(),
+        })
+    }
+
+    /// start the stream subscribers, only once
+    pub fn init(&'static self) {
+        if self.started.set(()).is_err() {
+            return;
+        }
+
+        self.subscribe_stream(
+            || "/traffic".into(),
+            |monitor, traffic: TrafficRes| {
+                let mut stats = monitor.stats.write();
+                stats.traffic = traffic;
+                if stats.traffic_history.len() >= TRAFFIC_HISTORY_LEN {
+                    stats.traffic_history.pop_front();
+                }
+                stats.traffic_history.push_back(traffic);
+                drop(stats);
+
+                handle::Handle::emit("verge://traffic", traffic);
+                monitor.broadcast(MonitorEvent::Traffic(traffic));
+            },
+        );
+
+        self.subscribe_stream(
+            || "/memory".into(),
+            |monitor, memory: MemoryRes| {
+                monitor.stats.write().memory = memory.inuse;
+                handle::Handle::emit("verge://memory", memory.inuse);
+                monitor.broadcast(MonitorEvent::Memory(memory.inuse));
+            },
+        );
+
+        let logs_path = || {
+            let level = Config::clash().latest().0.get("log-level").cloned();
+            let level = level.and_then(|l| l.as_str().map(String::from));
+            format!("/logs?level={}", level.unwrap_or("info".into()))
+        };
+        self.subscribe_stream(logs_path, |monitor, log: LogRes| {
+            let log = CoreLog {
+                time: chrono::Local::now().format("%H:%M:%S").to_string(),
+                log_type: log.log_type,
+                payload: log.payload,
+            };
+            let mut logs = monitor.logs.lock();
+            if logs.len() >= LOGS_QUEUE_LEN {
+                logs.pop_front();
+            }
+            logs.push_back(log.clone());
+            drop(logs);
+
+            handle::Handle::emit("verge://core-log", log.clone());
+            monitor.broadcast(MonitorEvent::Log(log));
+        });
+
+        self.poll_connections();
+    }
+
+    pub fn stats(&self) -> MonitorStats {
+        self.stats.read().clone()
+    }
+
+    pub fn logs(&self) -> VecDeque<CoreLog> {
+        self.logs.lock().clone()
+    }
+
+    /// receive the events in rust
+    pub fn subscribe(&self) -> broadcast::Receiver<MonitorEvent> {
+        self.sender.subscribe()
+    }
+
+    fn broadcast(&self, event: MonitorEvent) {
+        // it's ok that there is no receiver
+        let _ = self.sender.send(event);
+    }
+
+    /// keep reading the stream, reconnect after the core restarts
+    fn subscribe_stream<T, P, F>(&'static self, path: P, f: F)
+    where
+        T: DeserializeOwned,
+        P: Fn() -> String + Send + Sync + 'static,
+        F: Fn(&'static Monitor, T) + Send + Sync + 'static,
+    {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let path = path();
+                let result = match ClashApi::current() {
+                    Ok(api) => api
+                        .stream(&path, |item| f(self, item))
+                        .await
+                        .map_err(Into::into),
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = result {
+                    log::debug!(target: "app", "stream {path} disconnected: {err}");
+                }
+
+                // the core is stopped, reset the speed
+                if path == "/traffic" {
+                    self.stats.write().traffic = TrafficRes::default();
+                    handle::Handle::emit("verge://traffic", TrafficRes::default());
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+
+    /// poll the `/connections` snapshot, it's not a stream over plain http
+    fn poll_connections(&'static self) {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let result = match ClashApi::current() {
+                    Ok(api) => api.get_connections().await.map_err(Into::into),
+                    Err(err) => Err(err),
+                };
+                match result {
+                    Ok(conns) => {
+                        let mut stats = self.stats.write();
+                        stats.connections = conns.connections.len();
+                        stats.upload_total = conns.upload_total;
+                        stats.download_total = conns.download_total;
+                        let summary = (stats.connections, stats.upload_total, stats.download_total);
+                        drop(stats);
+
+                        handle::Handle::emit("verge://connections", summary);
+                        self.broadcast(MonitorEvent::Connections(Arc::new(conns)));
+                        tokio::time::sleep(CONNECTIONS_INTERVAL).await;
+                    }
+                    Err(err) => {
+                        log::debug!(target: "app", "failed to get the connections: {err}");
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,155 @@
+use super::{
+    clash_api::{ClashApi, ClashApiError},
+    logger::Logger,
+};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+
+/// 内核启动后等待外部控制可用的最长时间
+pub const READY_TIMEOUT: Duration = Duration::from_secs(10);
+const PROBE_INTERVAL: Duration = Duration::from_millis(200);
+
+const PORT_IN_USE_PATTERNS: [&str; 2] = [
+    "address already in use",
+    // windows
+    "only one usage of each socket address",
+];
+const BAD_CONFIG_PATTERNS: [&str; 3] = [
+    "parse config error",
+    "initial configuration error",
+    "yaml: ",
+];
+
+/// why the core is not ready
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreError {
+    /// the port of the proxy or the controller is used by another process
+    PortInUse(String),
+    /// the core rejects the config
+    BadConfig(String),
+    /// the core exited for other reasons
+    Exited(String),
+    /// the controller does not answer before the timeout
+    Unreachable(String),
+}
+
+impl fmt::Display for CoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PortInUse(msg) => write!(f, "port in use: {msg}"),
+            Self::BadConfig(msg) => write!(f, "bad config: {msg}"),
+            Self::Exited(msg) => write!(f, "the core exited: {msg}"),
+            Self::Unreachable(msg) => write!(f, "controller unreachable: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for CoreError {}
+
+/// find the reason in the core output, the last matched line wins
+pub fn classify(logs: &[String]) -> Option<CoreError> {
+    logs.iter().rev().find_map(|line| {
+        let lower = line.to_lowercase();
+        if PORT_IN_USE_PATTERNS.iter().any(|p| lower.contains(p)) {
+            Some(CoreError::PortInUse(line.clone()))
+        } else if BAD_CONFIG_PATTERNS.iter().any(|p| lower.contains(p)) {
+            Some(CoreError::BadConfig(line.clone()))
+        } else {
+            None
+        }
+    })
+}
+
+fn classify_output(fallback: CoreError) -> CoreError {
+    let logs = Vec::from(Logger::global().get_log());
+    classify(&logs).unwrap_or(fallback)
+}
+
+/// poll the controller until it answers
+/// `exit` receives the reason if the sidecar exits before it's ready,
+/// the output of the sidecar is checked only in this case
+pub async fn wait_ready(
+    api: &ClashApi,
+    timeout: Duration,
+    mut exit: Option<oneshot::Receiver<String>>,
+) -> Result<(), CoreError> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let error = match api.version().await {
+            Ok(_) => return Ok(()),
+            Err(err) => err,
+        };
+
+        if let Some(rx) = exit.as_mut() {
+            match rx.try_recv() {
+                Ok(reason) => return Err(classify_output(CoreError::Exited(reason))),
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    let reason = "unknown reason".into();
+                    return Err(classify_output(CoreError::Exited(reason)));
+                }
+                Err(oneshot::error::TryRecvError::Empty) => {}
+            }
+        }
+
+        if Instant::now() >= deadline {
+            let msg = match error {
+                // answered but refused, e.g. the secret is wrong
+                ClashApiError::Status { .. } => error.to_string(),
+                _ => format!("no response in {timeout:?}: {error}"),
+            };
+            let err = CoreError::Unreachable(msg);
+            return Err(match exit {
+                Some(_) => classify_output(err),
+                None => err,
+            });
+        }
+        tokio::time::sleep(PROBE_INTERVAL).await;
+    }
+}
+
+#[test]
+fn test_classify_output() {
+    let logs = |lines: &[&str]| lines.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    let port = logs(&[
+        "time=\"2024-01-01T00:00:00\" level=info msg=\"Start initial configuration in progress\"",
+        "time=\"2024-01-01T00:00:00\" level=error msg=\"Start Mixed(http+socks) server error: listen tcp 127.0.0.1:7897: bind: address already in use\"",
+    ]);
+    assert!(matches!(classify(&port), Some(CoreError::PortInUse(_))));
+
+    let windows = logs(&["listen tcp 127.0.0.1:9097: bind: Only one usage of each socket address (protocol/network address/port) is normally permitted."]);
+    assert!(matches!(classify(&windows), Some(CoreError::PortInUse(_))));
+
+    let config = logs(&[
+        "time=\"2024-01-01T00:00:00\" level=fatal msg=\"Parse config error: yaml: unmarshal errors:\"",
+    ]);
+    assert!(matches!(classify(&config), Some(CoreError::BadConfig(_))));
+
+    assert_eq!(
+        classify(&logs(&[
+            "level=info msg=\"RESTful API listening at: 127.0.0.1:9097\""
+        ])),
+        None
+    );
+    assert_eq!(classify(&[]), None);
+}
+
+#[tokio::test]
+async fn test_wait_ready() {
+    // nothing listens on the port
+    let api = ClashApi::new("http://127.0.0.1:1", None).unwrap();
+
+    let err = wait_ready(&api, Duration::from_millis(300), None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, CoreError::Unreachable(_)));
+
+    let (tx, rx) = oneshot::channel();
+    tx.send("exited with code Some(1)".to_string()).unwrap();
+    let err = wait_ready(&api, READY_TIMEOUT, Some(rx)).await.unwrap_err();
+    assert_eq!(err, CoreError::Exited("exited with code Some(1)".into()));
+}
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use sysinfo::{Pid, ProcessesToUpdate, System};
+
+/// the core process started by the app, saved in the pid file
+/// so the core left by a crashed app can be found without killing by name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreProcess {
+    pub pid: u32,
+    /// the start time and the binary tell whether the pid is reused
+    pub start_time: u64,
+    pub exe: Option<PathBuf>,
+    /// started by the service, stop it by the service
+    #[serde(default)]
+    pub service: bool,
+}
+
+impl CoreProcess {
+    /// get the process info from the system, none if it's not running
+    pub fn lookup(system: &System, pid: u32, service: bool) -> Option<Self> {
+        let process = system.process(Pid::from_u32(pid))?;
+        Some(Self {
+            pid,
+            start_time: process.start_time(),
+            exe: process.exe().map(|p| p.to_path_buf()),
+            service,
+        })
+    }
+
+    /// still the same process, not another one reusing the pid
+    pub fn is_alive(&self, system: &System) -> bool {
+        match Self::lookup(system, self.pid, self.service) {
+            Some(current) => {
+                current.start_time == self.start_time
+                    && (current.exe.is_none() || self.exe.is_none() || current.exe == self.exe)
+            }
+            None => false,
+        }
+    }
+
+    pub fn load(pid_file: &Path) -> Option<Self> {
+        let content = fs::read_to_string(pid_file).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, pid_file: &Path) -> Result<()> {
+        fs::write(pid_file, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn remove(pid_file: &Path) {
+        let _ = fs::remove_file(pid_file);
+    }
+}
+
+/// the process and its descendants, children first
+pub fn process_tree(parents: &[(u32, Option<u32>)], root: u32) -> Vec<u32> {
+    let mut tree = vec![root];
+    let mut index = 0;
+    while index < tree.len() {
+        let parent = tree[index];
+        let children = parents
+            .iter()
+            .filter(|(pid, ppid)| *ppid == Some(parent) && !tree.contains(pid))
+            .map(|(pid, _)| *pid)
+            .collect::<Vec<_>>();
+        tree.extend(children);
+        index += 1;
+    }
+    tree.reverse();
+    tree
+}
+
+fn refreshed_system() -> System {
+    let mut system = System::new();
+    system.refresh_processes(ProcessesToUpdate::All, true);
+    system
+}
+
+/// kill the process and its children
+pub fn kill_tree(pid: u32) {
+    let system = refreshed_system();
+    let parents = system
+        .processes()
+        .iter()
+        // the threads are listed as processes on linux
+        .filter(|(_, p)| p.thread_kind().is_none())
+        .map(|(pid, p)| (pid.as_u32(), p.parent().map(|p| p.as_u32())))
+        .collect::<Vec<_>>();
+
+    for pid in process_tree(&parents, pid) {
+        if let Some(process) = system.process(Pid::from_u32(pid)) {
+            log::debug!(target: "app", "kill the core process {pid}");
+            process.kill();
+        }
+    }
+}
+
+/// record the core process started just now
+pub fn track(pid_file: &Path, pid: u32, service: bool) {
+    let system = refreshed_system();
+    let process = CoreProcess::lookup(&system, pid, service).unwrap_or(CoreProcess {
+        pid,
+        start_time: 0,
+        exe: None,
+        service,
+    });
+    if let Err(err) = process.save(pid_file) {
+        log::error!(target: "app", "failed to save the pid file: {err}");
+    }
+}
+
+/// the core left by the last run of the app, if it's still running
+pub fn stale_core(pid_file: &Path) -> Option<CoreProcess> {
+    let process = CoreProcess::load(pid_file)?;
+    if process.is_alive(&refreshed_system()) {
+        Some(process)
+    } else {
+        // the pid is reused or the core is gone
+        CoreProcess::remove(pid_file);
+        None
+    }
+}
+
+#[test]
+fn test_process_tree() {
+    let parents = vec![
+        (1, None),
+        (10, Some(1)),
+        (11, Some(10)),
+        (12, Some(10)),
+        (13, Some(11)),
+        (20, Some(1)),
+    ];
+    assert_eq!(process_tree(&parents, 10), vec![13, 12, 11, 10]);
+    assert_eq!(process_tree(&parents, 20), vec![20]);
+    // not in the list
+    assert_eq!(process_tree(&parents, 99), vec![99]);
+}
+
+#[test]
+fn test_core_process() {
+    let system = refreshed_system();
+    let pid = std::process::id();
+    let current = CoreProcess::lookup(&system, pid, false).unwrap();
+    assert!(current.is_alive(&system));
+
+    // another process reusing the pid
+    let reused = CoreProcess {
+        start_time: current.start_time + 1,
+        ..current.clone()
+    };
+    assert!(!reused.is_alive(&system));
+
+    let json = serde_json::to_string(&current).unwrap();
+    assert_eq!(serde_json::from_str::<CoreProcess>(&json).unwrap(), current);
+}
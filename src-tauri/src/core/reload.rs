@@ -0,0 +1,91 @@
+use crate::config::CLASH_BASIC_CONFIG;
+use serde_yaml::Mapping;
+
+/// the settings of the external controller
+/// the core may not listen again when reloading, so it may need a restart
+const CONTROLLER_CONFIG: [&str; 10] = [
+    "external-controller",
+    "external-controller-tls",
+    "external-controller-unix",
+    "external-controller-pipe",
+    "external-controller-cors",
+    "external-ui",
+    "external-ui-name",
+    "external-ui-url",
+    "external-doh-server",
+    "secret",
+];
+
+/// the cheapest way to apply a changed key to the running core, from cheap to costly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReloadAction {
+    /// `PATCH /configs`, nothing is interrupted
+    Patch,
+    /// `PUT /configs` with the runtime config, the proxies and rules are reloaded
+    Put,
+    /// the controller changes, reload and reconnect,
+    /// restart only if the controller is lost
+    Restart,
+}
+
+impl ReloadAction {
+    pub fn of(key: &str) -> Self {
+        if CLASH_BASIC_CONFIG.contains(&key) {
+            Self::Patch
+        } else if CONTROLLER_CONFIG.contains(&key) {
+            Self::Restart
+        } else {
+            Self::Put
+        }
+    }
+}
+
+/// the keys applied by `PATCH /configs` and the action for the rest
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReloadPlan {
+    pub patch: Mapping,
+    /// none if all keys are patched
+    pub action: Option<ReloadAction>,
+}
+
+impl ReloadPlan {
+    pub fn new(changed: &Mapping) -> Self {
+        let mut plan = Self::default();
+        for (key, value) in changed.iter() {
+            let Some(name) = key.as_str() else {
+                continue;
+            };
+            match ReloadAction::of(name) {
+                ReloadAction::Patch => {
+                    plan.patch.insert(key.clone(), value.clone());
+                }
+                action => plan.action = plan.action.max(Some(action)),
+            }
+        }
+        plan
+    }
+}
+
+#[test]
+fn test_reload_plan() {
+    let plan = |yaml: &str| ReloadPlan::new(&serde_yaml::from_str(yaml).unwrap());
+
+    assert_eq!(ReloadAction::of("mixed-port"), ReloadAction::Patch);
+    assert_eq!(ReloadAction::of("unified-delay"), ReloadAction::Put);
+    assert_eq!(ReloadAction::of("dns"), ReloadAction::Put);
+    assert_eq!(ReloadAction::of("secret"), ReloadAction::Restart);
+
+    let routine = plan("mode: global\nallow-lan: true\ntun:\n  stack: gvisor\n");
+    assert_eq!(routine.patch.len(), 3);
+    assert_eq!(routine.action, None);
+
+    let mixed = plan("log-level: debug\nunified-delay: true\n");
+    assert_eq!(mixed.patch.len(), 1);
+    assert_eq!(mixed.action, Some(ReloadAction::Put));
+
+    let controller = plan("external-controller: 127.0.0.1:9098\nsecret: abc\nipv6: true\n");
+    assert_eq!(controller.patch.len(), 1);
+    assert_eq!(controller.action, Some(ReloadAction::Restart));
+
+    assert_eq!(plan("{}"), ReloadPlan::default());
+}
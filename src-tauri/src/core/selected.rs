@@ -0,0 +1,167 @@
+use super::clash_api::{ClashApi, ProxyItem};
+use crate::config::{Config, PrfItem, PrfSelected};
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// 可以手动选择节点的分组
+const SELECTABLE_TYPES: [&str; 3] = ["Selector", "URLTest", "Fallback"];
+
+/// the proxy chosen by the user, `fixed` for the url-test / fallback group
+fn chosen(group: &ProxyItem) -> Option<&String> {
+    match group.ptype.as_str() {
+        "Selector" => group.now.as_ref(),
+        _ => group.fixed.as_ref(),
+    }
+}
+
+/// drop the saved selections whose group or proxy no longer exists
+/// return the remaining selections and the `(group, proxy)` to be selected
+pub fn plan_restore(
+    saved: &[PrfSelected],
+    proxies: &HashMap<String, ProxyItem>,
+) -> (Vec<PrfSelected>, Vec<(String, String)>) {
+    let mut remain = vec![];
+    let mut changes = vec![];
+
+    for item in saved {
+        let (Some(name), Some(now)) = (&item.name, &item.now) else {
+            continue;
+        };
+        let Some(group) = proxies.get(name) else {
+            log::info!(target: "app", "the group {name} no longer exists");
+            continue;
+        };
+        if !SELECTABLE_TYPES.contains(&group.ptype.as_str()) {
+            continue;
+        }
+        if !group.all.iter().flatten().any(|p| p == now) {
+            log::info!(target: "app", "the proxy {now} no longer exists in the group {name}");
+            continue;
+        }
+        // 同名分组只保留第一个
+        if remain
+            .iter()
+            .any(|s: &PrfSelected| s.name.as_ref() == Some(name))
+        {
+            continue;
+        }
+
+        if chosen(group) != Some(now) {
+            changes.push((name.clone(), now.clone()));
+        }
+        remain.push(item.clone());
+    }
+    (remain, changes)
+}
+
+/// 恢复当前订阅保存的节点选择
+/// 在内核启动或者配置重载之后调用，api 需要已经可用
+pub async fn restore_selected(api: &ClashApi) -> Result<()> {
+    let (uid, saved) = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let Some(uid) = profiles.get_current() else {
+            return Ok(());
+        };
+        let saved = profiles.get_item(&uid)?.selected.clone();
+        (uid, saved.unwrap_or_default())
+    };
+    if saved.is_empty() {
+        return Ok(());
+    }
+
+    let proxies = api.get_proxies().await?;
+    let (remain, changes) = plan_restore(&saved, &proxies);
+
+    for (group, proxy) in changes {
+        match api.select_proxy(&group, &proxy).await {
+            Ok(_) => log::debug!(target: "app", "restore the group {group} to {proxy}"),
+            Err(err) => log::error!(target: "app", "failed to restore the group {group}: {err}"),
+        }
+    }
+
+    if remain.len() != saved.len() {
+        save_selected(uid, remain)?;
+    }
+    Ok(())
+}
+
+/// record the selection of the group into the current profile
+pub fn remember_selected(group: &str, proxy: &str) -> Result<()> {
+    let (uid, mut selected) = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let Some(uid) = profiles.get_current() else {
+            return Ok(());
+        };
+        let selected = profiles.get_item(&uid)?.selected.clone();
+        (uid, selected.unwrap_or_default())
+    };
+
+    let item = PrfSelected {
+        name: Some(group.into()),
+        now: Some(proxy.into()),
+    };
+    match selected
+        .iter_mut()
+        .find(|s| s.name.as_deref() == Some(group))
+    {
+        Some(each) => *each = item,
+        None => selected.push(item),
+    }
+    save_selected(uid, selected)
+}
+
+fn save_selected(uid: String, selected: Vec<PrfSelected>) -> Result<()> {
+    let item = PrfItem {
+        selected: Some(selected),
+        ..PrfItem::default()
+    };
+    Config::profiles().latest().patch_item(uid, item)
+}
+
+#[test]
+fn test_plan_restore() {
+    let group = |ptype: &str, all: &[&str], now: &str, fixed: Option<&str>| ProxyItem {
+        name: String::new(),
+        ptype: ptype.into(),
+        all: Some(all.iter().map(|s| s.to_string()).collect()),
+        now: Some(now.into()),
+        fixed: fixed.map(String::from),
+        ..ProxyItem::default()
+    };
+    let selected = |name: &str, now: &str| PrfSelected {
+        name: Some(name.into()),
+        now: Some(now.into()),
+    };
+
+    let proxies = HashMap::from([
+        ("Proxy".into(), group("Selector", &["a", "b"], "a", None)),
+        ("Auto".into(), group("URLTest", &["a", "b"], "a", None)),
+        ("Same".into(), group("Selector", &["a", "b"], "b", None)),
+        ("a".into(), ProxyItem::default()),
+    ]);
+    let saved = vec![
+        selected("Proxy", "b"),
+        selected("Auto", "b"),
+        selected("Same", "b"),
+        // the group was removed
+        selected("Removed", "a"),
+        // the proxy was removed
+        selected("Proxy", "c"),
+        // not a group
+        selected("a", "b"),
+        selected("Proxy", "a"),
+    ];
+
+    let (remain, changes) = plan_restore(&saved, &proxies);
+    let names = remain.iter().map(|s| s.name.clone().unwrap());
+    assert_eq!(names.collect::<Vec<_>>(), vec!["Proxy", "Auto", "Same"]);
+    assert_eq!(
+        changes,
+        vec![
+            ("Proxy".to_string(), "b".to_string()),
+            ("Auto".to_string(), "b".to_string())
+        ]
+    );
+}
@@ -1,10 +1,10 @@
-use crate::config::Config;
+use crate::core::binary::CoreBinary;
 use crate::utils::dirs;
 use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::{env::current_exe, process::Command as StdCommand};
+use std::process::Command as StdCommand;
 
 const SERVICE_URL: &str = "http://127.0.0.1:33211";
 
@@ -22,6 +22,9 @@ struct ClashInfo {
     pub config_dir: String,
     pub config_file: String,
     pub log_file: String,
+    /// the pid of the core, reported by the newer service
+    #[serde(default)]
+    pub pid: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -282,12 +285,8 @@ pub(super) async fn run_core_by_service(config_file: &PathBuf) -> Result<()> {
     check_service().await?;
     stop_core_by_service().await?;
 
-    let clash_core = Config::verge().latest().clash_core.clone();
-    let clash_core = clash_core.unwrap_or("verge-mihomo".into());
-
-    let bin_ext = if cfg!(windows) { ".exe" } else { "" };
-    let clash_bin = format!("{clash_core}{bin_ext}");
-    let bin_path = current_exe()?.with_file_name(clash_bin);
+    let binary = CoreBinary::current()?;
+    let bin_path = binary.path()?;
     let bin_path = dirs::path_to_str(&bin_path)?;
 
     let config_dir = dirs::app_home_dir()?;
@@ -299,7 +298,7 @@ pub(super) async fn run_core_by_service(config_file: &PathBuf) -> Result<()> {
     let config_file = dirs::path_to_str(config_file)?;
 
     let mut map = HashMap::new();
-    map.insert("core_type", clash_core.as_str());
+    map.insert("core_type", binary.name());
     map.insert("bin_path", bin_path);
     map.insert("config_dir", config_dir);
     map.insert("config_file", config_file);
@@ -324,6 +323,15 @@ pub(super) async fn run_core_by_service(config_file: &PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// the pid of the core started by the service
+pub(super) async fn core_pid_by_service() -> Result<Option<u32>> {
+    let res = check_service().await?;
+    Ok(res
+        .data
+        .and_then(|data| data.info)
+        .and_then(|info| info.pid))
+}
+
 /// stop the clash by service
 pub(super) async fn stop_core_by_service() -> Result<()> {
     let url = format!("{SERVICE_URL}/clash");
@@ -0,0 +1,213 @@
+use super::{handle, logger::Logger};
+use crate::{config::Config, utils::dirs};
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    fs,
+    time::{Duration, Instant},
+};
+
+/// 第一次重启前等待的时间，之后每次翻倍
+const BASE_DELAY: Duration = Duration::from_secs(1);
+/// 时间窗口内崩溃超过次数就不再重启
+const RESTART_WINDOW: Duration = Duration::from_secs(300);
+const MAX_RESTARTS: usize = 5;
+/// 崩溃报告里保留的内核输出行数
+const CRASH_LOG_LINES: usize = 50;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CoreState {
+    #[default]
+    Stopped,
+    Running,
+    /// waiting for the backoff to restart
+    Restarting,
+    /// crashed too many times in the window, need to restart manually
+    CrashLoop,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SupervisorStatus {
+    pub state: CoreState,
+    /// the crashes in the restart window
+    pub crashes: usize,
+    /// `%Y-%m-%d %H:%M:%S`
+    pub last_crash: Option<String>,
+    /// the path of the last crash report
+    pub crash_report: Option<String>,
+}
+
+/// the crash times in the restart window
+#[derive(Debug, Default)]
+pub struct RestartPolicy {
+    crashes: VecDeque<Instant>,
+}
+
+impl RestartPolicy {
+    /// record a crash, return the delay before restarting
+    /// or none if the core is crash-looping
+    pub fn on_crash(&mut self, now: Instant) -> Option<Duration> {
+        while let Some(first) = self.crashes.front() {
+            if now.duration_since(*first) < RESTART_WINDOW {
+                break;
+            }
+            self.crashes.pop_front();
+        }
+        self.crashes.push_back(now);
+
+        let count = self.crashes.len();
+        if count > MAX_RESTARTS {
+            return None;
+        }
+        Some(BASE_DELAY * 2u32.pow(count as u32 - 1))
+    }
+
+    pub fn count(&self) -> usize {
+        self.crashes.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.crashes.clear();
+    }
+}
+
+/// 监控内核进程，崩溃后按指数退避重启，频繁崩溃则停止重启并提示
+pub struct Supervisor {
+    policy: Mutex<RestartPolicy>,
+    status: Mutex<SupervisorStatus>,
+}
+
+impl Supervisor {
+    pub fn global() -> &'static Supervisor {
+        static SUPERVISOR: OnceCell<Supervisor> = OnceCell::new();
+
+        SUPERVISOR.get_or_init(|| Supervisor {
+            policy: Mutex::new(RestartPolicy::default()),
+            status: Mutex::new(SupervisorStatus::default()),
+        })
+    }
+
+    pub fn status(&self) -> SupervisorStatus {
+        self.status.lock().clone()
+    }
+
+    pub fn is_crash_looping(&self) -> bool {
+        self.status.lock().state == CoreState::CrashLoop
+    }
+
+    pub fn on_started(&self) {
+        self.set_state(CoreState::Running);
+    }
+
+    pub fn on_stopped(&self) {
+        self.set_state(CoreState::Stopped);
+    }
+
+    /// forget the crashes, when the user restarts the core manually
+    pub fn reset(&self) {
+        self.policy.lock().reset();
+        self.status.lock().crashes = 0;
+    }
+
+    /// write the crash report, return the delay before restarting
+    /// none if the core is crash-looping and should not be restarted
+    pub fn on_crash(&self, reason: &str) -> Option<Duration> {
+        log::error!(target: "app", "the core crashed: {reason}");
+
+        let report = match Self::write_report(reason) {
+            Ok(path) => Some(path),
+            Err(err) => {
+                log::error!(target: "app", "failed to write the crash report: {err}");
+                None
+            }
+        };
+
+        let (delay, crashes) = {
+            let mut policy = self.policy.lock();
+            (policy.on_crash(Instant::now()), policy.count())
+        };
+        {
+            let mut status = self.status.lock();
+            status.crashes = crashes;
+            status.last_crash = Some(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+            status.crash_report = report.or(status.crash_report.take());
+        }
+
+        match delay {
+            Some(delay) => {
+                log::info!(target: "app", "restart the core after {delay:?}");
+                self.set_state(CoreState::Restarting);
+            }
+            None => {
+                log::error!(target: "app", "the core is crash-looping, stop restarting");
+                self.set_state(CoreState::CrashLoop);
+                handle::Handle::notice_message(
+                    "set_config::error",
+                    "the core is crash-looping, please check the crash report",
+                );
+            }
+        }
+        delay
+    }
+
+    fn set_state(&self, state: CoreState) {
+        let status = {
+            let mut status = self.status.lock();
+            if status.state == state {
+                return;
+            }
+            status.state = state;
+            status.clone()
+        };
+        handle::Handle::emit("verge://core-state", status);
+        let _ = handle::Handle::update_systray_part();
+    }
+
+    /// save the reason and the last lines of the core output
+    fn write_report(reason: &str) -> Result<String> {
+        let path = dirs::crash_report_file()?;
+        let clash_core = Config::verge().latest().clash_core.clone();
+        let logs = Logger::global().get_log();
+        let skip = logs.len().saturating_sub(CRASH_LOG_LINES);
+
+        let mut report = format!(
+            "time: {}\ncore: {}\nreason: {reason}\n\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            clash_core.unwrap_or("verge-mihomo".into()),
+        );
+        for line in logs.iter().skip(skip) {
+            report.push_str(line);
+            report.push('\n');
+        }
+        fs::write(&path, report)?;
+        Ok(dirs::path_to_str(&path)?.to_string())
+    }
+}
+
+#[test]
+fn test_restart_policy() {
+    let start = Instant::now();
+    let at = |secs: u64| start + Duration::from_secs(secs);
+    let mut policy = RestartPolicy::default();
+
+    // exponential backoff
+    assert_eq!(policy.on_crash(at(0)), Some(Duration::from_secs(1)));
+    assert_eq!(policy.on_crash(at(2)), Some(Duration::from_secs(2)));
+    assert_eq!(policy.on_crash(at(5)), Some(Duration::from_secs(4)));
+    assert_eq!(policy.on_crash(at(10)), Some(Duration::from_secs(8)));
+    assert_eq!(policy.on_crash(at(20)), Some(Duration::from_secs(16)));
+    // too many crashes in the window
+    assert_eq!(policy.on_crash(at(40)), None);
+    assert_eq!(policy.count(), 6);
+
+    // the old crashes are out of the window
+    assert_eq!(policy.on_crash(at(330)), Some(Duration::from_secs(2)));
+    assert_eq!(policy.count(), 2);
+
+    policy.reset();
+    assert_eq!(policy.on_crash(at(331)), Some(Duration::from_secs(1)));
+}
@@ -1,4 +1,5 @@
 use crate::config::Config;
+use crate::core::latency::LatencyTester;
 use crate::feat;
 use anyhow::{Context, Result};
 use delay_timer::prelude::{DelayTimer, DelayTimerBuilder, TaskBuilder};
@@ -18,6 +19,9 @@ pub struct Timer {
 
     /// increment id
     timer_count: Arc<Mutex<TaskID>>,
+
+    /// the batch latency test task -> (task id, interval)
+    latency_task: Arc<Mutex<Option<(TaskID, u64)>>>,
 }
 
 impl Timer {
@@ -28,12 +32,14 @@ impl Timer {
             delay_timer: Arc::new(Mutex::new(DelayTimerBuilder::default().build())),
             timer_map: Arc::new(Mutex::new(HashMap::new())),
             timer_count: Arc::new(Mutex::new(1)),
+            latency_task: Arc::new(Mutex::new(None)),
         })
     }
 
     /// restore timer
     pub fn init(&self) -> Result<()> {
         self.refresh_profiles()?;
+        self.refresh_latency_test()?;
 
         let cur_timestamp = chrono::Local::now().timestamp();
 
@@ -94,6 +100,43 @@ impl Timer {
         Ok(())
     }
 
+    /// update the batch latency test task when the interval is changed
+    pub fn refresh_latency_test(&self) -> Result<()> {
+        let minutes = Config::verge().latest().latency_test_interval.unwrap_or(0);
+
+        let mut latency_task = self.latency_task.lock();
+        let delay_timer = self.delay_timer.lock();
+
+        if let Some((tid, val)) = *latency_task {
+            if val == minutes {
+                return Ok(());
+            }
+            crate::log_err!(delay_timer.remove_task(tid));
+            *latency_task = None;
+        }
+        if minutes == 0 {
+            return Ok(());
+        }
+
+        let tid = {
+            let mut count = self.timer_count.lock();
+            *count += 1;
+            *count - 1
+        };
+        let task = TaskBuilder::default()
+            .set_task_id(tid)
+            .set_maximum_parallel_runnable_num(1)
+            .set_frequency_repeated_by_minutes(minutes)
+            .spawn_async_routine(Self::latency_test_task)
+            .context("failed to create timer task")?;
+        delay_timer
+            .add_task(task)
+            .context("failed to add timer task")?;
+
+        *latency_task = Some((tid, minutes));
+        Ok(())
+    }
+
     /// generate a map -> (uid, update_interval)
     fn gen_profiles_interval(&self) -> HashMap<String, u64> {
         let mut new_map = HashMap::new();
@@ -197,6 +240,13 @@ impl Timer {
         log::info!(target: "app", "running timer task `{uid}`");
         crate::log_err!(feat::update_profile(uid, None).await);
     }
+
+    async fn latency_test_task() {
+        let group = Config::verge().latest().latency_test_group.clone();
+        let group = group.filter(|g| !g.is_empty());
+        log::info!(target: "app", "running the batch latency test");
+        crate::log_err!(LatencyTester::global().test_all(group.as_deref()).await);
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,401 @@
+use super::{
+    clash_api::Connections,
+    monitor::{Monitor, MonitorEvent},
+};
+use crate::{config::Config, log_err, utils::dirs};
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::broadcast::error::RecvError;
+
+/// 每分钟写一次磁盘
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+/// 只保留最近的记录
+const KEEP_DAYS: i64 = 180;
+
+/// upload and download of one (date, profile, chains, process)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TrafficRecord {
+    /// `%Y-%m-%d`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// the uid of the current profile
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// the proxy chain, from the outbound proxy to the group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chains: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<String>,
+    pub upload: u64,
+    pub download: u64,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct TrafficQuery {
+    /// `%Y-%m-%d`, inclusive
+    pub start: Option<String>,
+    /// `%Y-%m-%d`, inclusive
+    pub end: Option<String>,
+    /// any of `date` | `profile` | `chains` | `process`
+    /// the fields not in the list would be summed up
+    #[serde(default)]
+    pub group_by: Vec<String>,
+}
+
+type RecordKey = (String, String, Vec<String>, String);
+
+/// the connection in the last snapshot
+#[derive(Debug, Default)]
+struct LastSeen {
+    upload: u64,
+    download: u64,
+    chains: Vec<String>,
+    process: String,
+}
+
+#[derive(Debug, Default)]
+pub struct TrafficStore {
+    records: BTreeMap<RecordKey, (u64, u64)>,
+    last: HashMap<String, LastSeen>,
+    /// the `upload_total` and `download_total` of the last snapshot
+    last_total: Option<(u64, u64)>,
+    dirty: bool,
+}
+
+impl TrafficStore {
+    /// add the bytes transferred since the last snapshot
+    /// the connections closed since then are counted with the totals of the core
+    pub fn record(&mut self, date: &str, profile: &str, snapshot: &Connections) {
+        let mut last = HashMap::with_capacity(snapshot.connections.len());
+        let (mut live_up, mut live_down) = (0, 0);
+
+        for conn in snapshot.connections.iter() {
+            let seen = self.last.remove(&conn.id).unwrap_or_default();
+            let up = conn.upload.saturating_sub(seen.upload);
+            let down = conn.download.saturating_sub(seen.download);
+            live_up += up;
+            live_down += down;
+
+            let seen = LastSeen {
+                upload: conn.upload,
+                download: conn.download,
+                chains: conn.chains.clone(),
+                process: conn.metadata.process.clone(),
+            };
+            self.add(date, profile, &seen, up, down);
+            last.insert(conn.id.clone(), seen);
+        }
+
+        // the bytes of the closed connections after the last snapshot are only in the totals
+        // the totals are reset when the core restarts
+        if let Some((total_up, total_down)) = self.last_total {
+            let delta = |now: u64, before: u64| if now < before { now } else { now - before };
+            let closed_up = delta(snapshot.upload_total, total_up).saturating_sub(live_up);
+            let closed_down = delta(snapshot.download_total, total_down).saturating_sub(live_down);
+
+            let mut closed = std::mem::take(&mut self.last)
+                .into_values()
+                .collect::<Vec<_>>();
+            if closed.is_empty() {
+                // opened and closed between the two snapshots
+                closed.push(LastSeen::default());
+            }
+            let ups = split(
+                closed_up,
+                &closed.iter().map(|c| c.upload).collect::<Vec<_>>(),
+            );
+            let downs = split(
+                closed_down,
+                &closed.iter().map(|c| c.download).collect::<Vec<_>>(),
+            );
+            for ((seen, up), down) in closed.iter().zip(ups).zip(downs) {
+                self.add(date, profile, seen, up, down);
+            }
+        }
+
+        self.last = last;
+        self.last_total = Some((snapshot.upload_total, snapshot.download_total));
+    }
+
+    fn add(&mut self, date: &str, profile: &str, seen: &LastSeen, up: u64, down: u64) {
+        if up == 0 && down == 0 {
+            return;
+        }
+        let key = (
+            date.to_string(),
+            profile.to_string(),
+            seen.chains.clone(),
+            seen.process.clone(),
+        );
+        let entry = self.records.entry(key).or_default();
+        entry.0 += up;
+        entry.1 += down;
+        self.dirty = true;
+    }
+
+    pub fn query(&self, query: &TrafficQuery) -> Vec<TrafficRecord> {
+        let by = |field: &str| query.group_by.iter().any(|g| g == field);
+        let pick = |field: &str, value: &String| by(field).then(|| value.clone());
+        let pick_chains = |chains: &Vec<String>| by("chains").then(|| chains.clone());
+
+        let mut result: BTreeMap<RecordKey, TrafficRecord> = BTreeMap::new();
+        for ((date, profile, chains, process), (up, down)) in self.records.iter() {
+            if query.start.as_ref().is_some_and(|start| date < start) {
+                continue;
+            }
+            if query.end.as_ref().is_some_and(|end| date > end) {
+                continue;
+            }
+
+            let record = TrafficRecord {
+                date: pick("date", date),
+                profile: pick("profile", profile),
+                chains: pick_chains(chains),
+                process: pick("process", process),
+                ..TrafficRecord::default()
+            };
+            let key = (
+                record.date.clone().unwrap_or_default(),
+                record.profile.clone().unwrap_or_default(),
+                record.chains.clone().unwrap_or_default(),
+                record.process.clone().unwrap_or_default(),
+            );
+            let entry = result.entry(key).or_insert(record);
+            entry.upload += up;
+            entry.download += down;
+        }
+        result.into_values().collect()
+    }
+
+    /// remove the records before the date
+    pub fn prune(&mut self, before: &str) {
+        let len = self.records.len();
+        self.records.retain(|(date, ..), _| date.as_str() >= before);
+        self.dirty |= len != self.records.len();
+    }
+
+    fn from_records(records: Vec<TrafficRecord>) -> Self {
+        let mut store = Self::default();
+        for record in records {
+            let key = (
+                record.date.unwrap_or_default(),
+                record.profile.unwrap_or_default(),
+                record.chains.unwrap_or_default(),
+                record.process.unwrap_or_default(),
+            );
+            let entry = store.records.entry(key).or_default();
+            entry.0 += record.upload;
+            entry.1 += record.download;
+        }
+        store
+    }
+
+    fn to_records(&self) -> Vec<TrafficRecord> {
+        let query = TrafficQuery {
+            group_by: ["date", "profile", "chains", "process"]
+                .map(String::from)
+                .to_vec(),
+            ..TrafficQuery::default()
+        };
+        self.query(&query)
+    }
+}
+
+/// 按天、订阅、代理链、进程统计流量，保存在 `traffic.json`
+pub struct TrafficStats {
+    store: Arc<Mutex<TrafficStore>>,
+    started: OnceCell<()>,
+}
+
+impl TrafficStats {
+    pub fn global() -> &'static TrafficStats {
+        static TRAFFIC: OnceCell<TrafficStats> = OnceCell::new();
+
+        TRAFFIC.get_or_init(|| TrafficStats {
+            store: Arc::new(Mutex::new(TrafficStore::default())),
+            started: OnceCell::new(),
+        })
+    }
+
+    /// load the records and start to count the connections from the monitor
+    pub fn init(&'static self) -> Result<()> {
+        if self.started.set(()).is_err() {
+            return Ok(());
+        }
+
+        let path = dirs::traffic_path()?;
+        if path.exists() {
+            let data = fs::read(&path).context("failed to read the traffic records")?;
+            let records = serde_json::from_slice::<Vec<TrafficRecord>>(&data);
+            match records {
+                Ok(records) => *self.store.lock() = TrafficStore::from_records(records),
+                Err(err) => log::error!(target: "app", "failed to parse traffic records: {err}"),
+            }
+        }
+
+        let mut receiver = Monitor::global().subscribe();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(MonitorEvent::Connections(conns)) => {
+                        let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+                        let profile = Config::profiles().latest().get_current();
+                        let profile = profile.unwrap_or_default();
+                        self.store.lock().record(&date, &profile, &conns);
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        tauri::async_runtime::spawn(async move {
+            loop {
+                tokio::time::sleep(SAVE_INTERVAL).await;
+                log_err!(self.save());
+            }
+        });
+        Ok(())
+    }
+
+    pub fn query(&self, query: &TrafficQuery) -> Vec<TrafficRecord> {
+        self.store.lock().query(query)
+    }
+
+    /// save to the disk if changed
+    pub fn save(&self) -> Result<()> {
+        let records = {
+            let mut store = self.store.lock();
+            let before = chrono::Local::now() - chrono::Duration::days(KEEP_DAYS);
+            store.prune(&before.format("%Y-%m-%d").to_string());
+            if !store.dirty {
+                return Ok(());
+            }
+            store.dirty = false;
+            store.to_records()
+        };
+
+        // write to a temp file first, a crash while writing should not lose the records
+        let data = serde_json::to_vec(&records)?;
+        let path = dirs::traffic_path()?;
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, data).context("failed to save the traffic records")?;
+        fs::rename(&temp, &path).context("failed to save the traffic records")
+    }
+}
+
+/// split the amount by the weights, evenly if all the weights are zero
+fn split(amount: u64, weights: &[u64]) -> Vec<u64> {
+    let sum = weights.iter().map(|w| *w as u128).sum::<u128>();
+    let mut rest = amount;
+    let mut result = weights
+        .iter()
+        .map(|w| {
+            let share = match sum {
+                0 => amount as u128 / weights.len() as u128,
+                _ => amount as u128 * *w as u128 / sum,
+            } as u64;
+            rest -= share;
+            share
+        })
+        .collect::<Vec<_>>();
+    if let Some(last) = result.last_mut() {
+        *last += rest;
+    }
+    result
+}
+
+#[test]
+fn test_traffic_store() {
+    use super::clash_api::Connection;
+
+    let conn = |id: &str, proxy: &str, process: &str, upload: u64, download: u64| {
+        let mut conn = Connection {
+            id: id.into(),
+            upload,
+            download,
+            chains: vec![proxy.into(), "GLOBAL".into()],
+            ..Connection::default()
+        };
+        conn.metadata.process = process.into();
+        conn
+    };
+    let snapshot =
+        |connections: Vec<Connection>, upload_total: u64, download_total: u64| Connections {
+            connections,
+            upload_total,
+            download_total,
+            ..Connections::default()
+        };
+
+    let mut store = TrafficStore::default();
+    store.record(
+        "2024-01-01",
+        "p1",
+        &snapshot(
+            vec![
+                conn("1", "a", "curl", 10, 100),
+                conn("2", "b", "curl", 1, 1),
+            ],
+            11,
+            101,
+        ),
+    );
+    // only the delta is counted
+    // `2` is closed after sending 2 and receiving 1 more bytes
+    store.record(
+        "2024-01-01",
+        "p1",
+        &snapshot(
+            vec![conn("1", "a", "curl", 15, 150), conn("3", "a", "git", 5, 5)],
+            23,
+            157,
+        ),
+    );
+    // the core restarts, the totals are reset
+    store.record(
+        "2024-01-02",
+        "p2",
+        &snapshot(vec![conn("4", "a", "curl", 7, 7)], 7, 7),
+    );
+
+    let query = |start: Option<&str>, group_by: &[&str]| TrafficQuery {
+        start: start.map(String::from),
+        end: None,
+        group_by: group_by.iter().map(|s| s.to_string()).collect(),
+    };
+    let total = |records: Vec<TrafficRecord>| {
+        let records = records
+            .into_iter()
+            .map(|r| (r.chains, r.date, r.upload, r.download));
+        records.collect::<Vec<_>>()
+    };
+    let chains = |proxy: &str| Some(vec![proxy.to_string(), "GLOBAL".into()]);
+
+    assert_eq!(
+        total(store.query(&query(None, &["chains"]))),
+        vec![(chains("a"), None, 27, 162), (chains("b"), None, 3, 2)]
+    );
+    assert_eq!(
+        total(store.query(&query(Some("2024-01-02"), &["date"]))),
+        vec![(None, Some("2024-01-02".into()), 7, 7)]
+    );
+
+    // save and load
+    let loaded = TrafficStore::from_records(store.to_records());
+    assert_eq!(loaded.records, store.records);
+
+    store.prune("2024-01-02");
+    assert_eq!(store.query(&query(None, &[])).len(), 1);
+
+    assert_eq!(split(10, &[1, 3]), vec![2, 8]);
+    assert_eq!(split(10, &[0, 0, 0]), vec![3, 3, 4]);
+}
@@ -1,6 +1,7 @@
 use crate::{
     cmds,
     config::Config,
+    core::supervisor::Supervisor,
     feat,
     utils::{dirs, resolve},
 };
@@ -316,6 +317,14 @@ impl Tray {
             service_mode_menu.set_title(t!("Service Mode", "服务模式"))?;
         }
 
+        // 内核频繁崩溃，提示手动重启
+        let restart_clash_menu = tray.get_item("restart_clash");
+        if Supervisor::global().is_crash_looping() {
+            restart_clash_menu.set_title(t!("Restart Clash (Crashed)", "重启 Clash（已崩溃）"))?;
+        } else {
+            restart_clash_menu.set_title(t!("Restart Clash", "重启 Clash"))?;
+        }
+
         #[cfg(target_os = "macos")]
         {
             let tray_icon = verge.tray_icon.unwrap_or("monochrome".to_string());
@@ -0,0 +1,482 @@
+use super::{
+    binary::{CoreBinary, CoreVersion},
+    supervisor::{CoreState, Supervisor},
+    CoreManager,
+};
+use crate::config::Config;
+use anyhow::{bail, Context, Result};
+use minisign_verify::{PublicKey, Signature};
+use once_cell::sync::OnceCell;
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+/// 解压后的内核不会超过这个大小，避免压缩炸弹
+const MAX_BINARY_SIZE: u64 = 256 * 1024 * 1024;
+
+/// where to download the core and how to verify it
+#[derive(Debug, Clone, Default)]
+pub struct UpdateSource {
+    /// the archive, `.gz` `.zip` or the binary itself
+    pub url: String,
+    /// hex sha256 of the archive, fetched from `{url}.sha256` if none
+    pub sha256: Option<String>,
+    /// minisign public key, verify `{url}.minisig` if set
+    pub pubkey: Option<String>,
+}
+
+/// download the archive and verify it
+pub async fn fetch_archive(client: &Client, source: &UpdateSource) -> Result<Vec<u8>> {
+    let url = source.url.as_str();
+    let archive = fetch(client, url, MAX_BINARY_SIZE).await?;
+
+    let expected = match source.sha256.as_ref() {
+        Some(sha256) => sha256.clone(),
+        None => {
+            let text = fetch(client, &format!("{url}.sha256"), MAX_BINARY_SIZE).await?;
+            String::from_utf8_lossy(&text).to_string()
+        }
+    };
+    verify_sha256(&archive, &expected)?;
+
+    if let Some(pubkey) = source.pubkey.as_ref().filter(|k| !k.trim().is_empty()) {
+        let signature = fetch(client, &format!("{url}.minisig"), MAX_BINARY_SIZE).await?;
+        verify_signature(&archive, pubkey, &String::from_utf8_lossy(&signature))?;
+    }
+    Ok(archive)
+}
+
+/// fail if the file is larger than `limit`
+async fn fetch(client: &Client, url: &str, limit: u64) -> Result<Vec<u8>> {
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("failed to download {url}"))?;
+    if !response.status().is_success() {
+        bail!("failed to download {url}: {}", response.status());
+    }
+    if response.content_length().is_some_and(|len| len > limit) {
+        bail!("failed to download {url}: the file is larger than {limit} bytes");
+    }
+
+    // the content length may be missing, check the size while reading
+    let mut bytes = vec![];
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .with_context(|| format!("failed to download {url}"))?
+    {
+        if (bytes.len() + chunk.len()) as u64 > limit {
+            bail!("failed to download {url}: the file is larger than {limit} bytes");
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// `expected` can be the content of a `.sha256` file, like `{hash}  {file name}`
+pub fn verify_sha256(bytes: &[u8], expected: &str) -> Result<()> {
+    let expected = expected
+        .split_whitespace()
+        .next()
+        .filter(|h| h.len() == 64 && h.chars().all(|c| c.is_ascii_hexdigit()))
+        .context("invalid sha256 checksum")?;
+
+    let actual = Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("sha256 mismatch, expected {expected}, got {actual}");
+    }
+    Ok(())
+}
+
+/// `pubkey` is the key line or the whole `minisign.pub`
+pub fn verify_signature(bytes: &[u8], pubkey: &str, signature: &str) -> Result<()> {
+    let pubkey = pubkey.trim();
+    let pubkey = match pubkey.lines().count() {
+        1 => PublicKey::from_base64(pubkey),
+        _ => PublicKey::decode(pubkey),
+    }
+    .map_err(|err| anyhow::anyhow!("invalid public key: {err}"))?;
+    let signature = Signature::decode(signature.trim())
+        .map_err(|err| anyhow::anyhow!("invalid signature: {err}"))?;
+
+    pubkey
+        .verify(bytes, &signature, false)
+        .map_err(|err| anyhow::anyhow!("signature verification failed: {err}"))
+}
+
+/// get the binary from the archive by the magic number
+/// fail if the binary is larger than `limit`
+pub fn extract(archive: &[u8], limit: u64) -> Result<Vec<u8>> {
+    let mut binary = vec![];
+    // read one more byte to know if it's over the limit
+    match archive {
+        [0x1f, 0x8b, ..] => {
+            flate2::read::GzDecoder::new(archive)
+                .take(limit + 1)
+                .read_to_end(&mut binary)?;
+        }
+        [b'P', b'K', 0x03, 0x04, ..] => {
+            let mut zip = zip::ZipArchive::new(Cursor::new(archive))?;
+            // the first file, the archives of the core contain only the binary
+            let index = (0..zip.len())
+                .find(|i| zip.by_index(*i).is_ok_and(|f| f.is_file()))
+                .context("no file in the zip archive")?;
+            zip.by_index(index)?
+                .take(limit + 1)
+                .read_to_end(&mut binary)?;
+        }
+        _ => binary.extend_from_slice(archive),
+    }
+    if binary.is_empty() {
+        bail!("the core binary is empty");
+    }
+    if binary.len() as u64 > limit {
+        bail!("the core binary is larger than {limit} bytes");
+    }
+    Ok(binary)
+}
+
+/// replace `target` with `staged` and keep the old one as `backup`
+pub fn swap(staged: &Path, target: &Path, backup: &Path) -> Result<()> {
+    if backup.exists() {
+        fs::remove_file(backup)?;
+    }
+
+    // 先保留旧内核，再用 rename 原子替换
+    #[cfg(not(windows))]
+    {
+        if target.exists() {
+            fs::hard_link(target, backup).or_else(|_| fs::copy(target, backup).map(|_| ()))?;
+        }
+        fs::rename(staged, target)?;
+    }
+
+    // windows 不能覆盖运行中的程序，但可以重命名
+    #[cfg(windows)]
+    {
+        if target.exists() {
+            fs::rename(target, backup)?;
+        }
+        if let Err(err) = fs::rename(staged, target) {
+            if backup.exists() {
+                let _ = fs::rename(backup, target);
+            }
+            bail!("failed to replace the core: {err}");
+        }
+    }
+    Ok(())
+}
+
+/// restore the backup to `target`
+pub fn restore(target: &Path, backup: &Path) -> Result<()> {
+    if !backup.exists() {
+        bail!("no backup of the core");
+    }
+    #[cfg(windows)]
+    if target.exists() {
+        // the running one can be renamed but not replaced
+        let replaced = target.with_extension("old.exe");
+        let _ = fs::remove_file(&replaced);
+        fs::rename(target, &replaced)?;
+    }
+    fs::rename(backup, target)?;
+    Ok(())
+}
+
+/// the paths of the staged binary and the backup next to the sidecar
+fn sibling_paths(target: &Path) -> Result<(PathBuf, PathBuf)> {
+    let stem = target
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .context("invalid core path")?;
+    let ext = if cfg!(windows) { ".exe" } else { "" };
+    Ok((
+        target.with_file_name(format!("{stem}.new{ext}")),
+        target.with_file_name(format!("{stem}.bak{ext}")),
+    ))
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+/// 更新内置的 sidecar 内核，不需要更新整个 app
+pub struct CoreUpdater {
+    /// only one update at a time
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl CoreUpdater {
+    pub fn global() -> &'static CoreUpdater {
+        static UPDATER: OnceCell<CoreUpdater> = OnceCell::new();
+
+        UPDATER.get_or_init(|| CoreUpdater {
+            lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    fn sidecar() -> Result<(CoreBinary, PathBuf)> {
+        let binary = CoreBinary::current()?;
+        if !matches!(binary, CoreBinary::Sidecar(_)) {
+            bail!("only the builtin core can be updated");
+        }
+        let path = binary.path()?;
+        Ok((binary, path))
+    }
+
+    fn client() -> Result<Client> {
+        let mut builder = reqwest::ClientBuilder::new()
+            .use_rustls_tls()
+            .no_proxy()
+            .timeout(DOWNLOAD_TIMEOUT);
+        // 内核在运行的话走代理下载
+        if Supervisor::global().status().state == CoreState::Running {
+            let port = Config::clash().latest().get_mixed_port();
+            if let Ok(proxy) = reqwest::Proxy::all(format!("http://127.0.0.1:{port}")) {
+                builder = builder.proxy(proxy);
+            }
+        }
+        Ok(builder.build()?)
+    }
+
+    /// download, verify and check the new core, then swap it in and restart
+    /// the old core is kept for rollback
+    pub async fn update(&self, url: Option<String>, sha256: Option<String>) -> Result<CoreVersion> {
+        let _guard = self.lock.try_lock().context("the core is updating")?;
+
+        let (binary, target) = Self::sidecar()?;
+        let (staged, backup) = sibling_paths(&target)?;
+        let source = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            UpdateSource {
+                url: url
+                    .or(verge.core_update_url.clone())
+                    .filter(|u| !u.trim().is_empty())
+                    .context("the url of the core update is not set")?,
+                sha256,
+                pubkey: verge.core_update_pubkey.clone(),
+            }
+        };
+
+        log::info!(target: "app", "download the core from {}", source.url);
+        let archive = fetch_archive(&Self::client()?, &source).await?;
+        fs::write(&staged, extract(&archive, MAX_BINARY_SIZE)?)?;
+        #[cfg(unix)]
+        set_executable(&staged)?;
+
+        // 新内核能跑并且能通过当前订阅的检查才替换
+        let candidate = CoreBinary::Custom {
+            name: binary.name().into(),
+            path: staged.clone(),
+        };
+        let checked = candidate.detect_version().and_then(|version| {
+            CoreManager::global().check_config_with(&candidate)?;
+            Ok(version)
+        });
+        let version = match checked {
+            Ok(version) => version,
+            Err(err) => {
+                let _ = fs::remove_file(&staged);
+                return Err(err);
+            }
+        };
+
+        swap(&staged, &target, &backup)?;
+        CoreBinary::refresh_current_version();
+        log::info!(target: "app", "the core is updated to {}", version.version);
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        Self::grant_permission(binary.name()).await;
+
+        Supervisor::global().reset();
+        if let Err(err) = CoreManager::global().run_core().await {
+            log::error!(target: "app", "the new core failed to start, roll back: {err}");
+            restore(&target, &backup)?;
+            CoreBinary::refresh_current_version();
+            CoreManager::global().run_core().await?;
+            bail!("the new core failed to start: {err}");
+        }
+        Ok(version)
+    }
+
+    /// the permission of the old binary is lost after the swap
+    /// grant it again if the tun mode needs it, tell the user if it fails
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    async fn grant_permission(name: &str) {
+        let need = {
+            let service = Config::verge().latest().enable_service_mode;
+            !service.unwrap_or(false) && Config::clash().latest().get_enable_tun()
+        };
+        if !need {
+            log::warn!(target: "app", "grant the permission again if the tun mode is used");
+            return;
+        }
+
+        let name = name.to_string();
+        let granted =
+            tauri::async_runtime::spawn_blocking(move || super::manager::grant_permission(name))
+                .await;
+        let err = match granted {
+            Ok(Ok(_)) => return,
+            Ok(Err(err)) => err.to_string(),
+            Err(err) => err.to_string(),
+        };
+        log::error!(target: "app", "failed to grant the permission to the new core: {err}");
+        super::handle::Handle::notice_message(
+            "set_config::error",
+            format!(
+                "failed to grant the permission to the new core, the tun mode may not work: {err}"
+            ),
+        );
+    }
+
+    /// put the previous core back and restart
+    pub async fn rollback(&self) -> Result<()> {
+        let _guard = self.lock.try_lock().context("the core is updating")?;
+
+        let (_, target) = Self::sidecar()?;
+        let (_, backup) = sibling_paths(&target)?;
+        restore(&target, &backup)?;
+        CoreBinary::refresh_current_version();
+
+        Supervisor::global().reset();
+        CoreManager::global().run_core().await
+    }
+}
+
+#[test]
+fn test_verify_archive() {
+    // sha256 of "test"
+    let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    assert!(verify_sha256(b"test", hash).is_ok());
+    assert!(verify_sha256(b"test", &format!("{}  mihomo.gz\n", hash.to_uppercase())).is_ok());
+    assert!(verify_sha256(b"tset", hash).is_err());
+    assert!(verify_sha256(b"test", "not a hash").is_err());
+
+    // the test vector of minisign
+    let pubkey = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    let signature = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==";
+    assert!(verify_signature(b"test", pubkey, signature).is_ok());
+    assert!(verify_signature(b"tset", pubkey, signature).is_err());
+    assert!(verify_signature(b"test", "bad key", signature).is_err());
+}
+
+#[test]
+fn test_extract_and_swap() {
+    use std::io::Write;
+
+    let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    gz.write_all(b"new core").unwrap();
+    let gz = gz.finish().unwrap();
+    assert_eq!(extract(&gz, 8).unwrap(), b"new core");
+    // too large after decompressing
+    assert!(extract(&gz, 7).is_err());
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+    zip.add_directory("mihomo/", zip::write::SimpleFileOptions::default())
+        .unwrap();
+    zip.start_file(
+        "mihomo/mihomo.exe",
+        zip::write::SimpleFileOptions::default(),
+    )
+    .unwrap();
+    zip.write_all(b"new core").unwrap();
+    let zip = zip.finish().unwrap().into_inner();
+    assert_eq!(extract(&zip, 8).unwrap(), b"new core");
+    assert!(extract(&zip, 7).is_err());
+
+    assert_eq!(extract(b"raw binary", 10).unwrap(), b"raw binary");
+    assert!(extract(b"raw binary", 9).is_err());
+    assert!(extract(b"", 10).is_err());
+
+    let dir = std::env::temp_dir().join(format!("verge-updater-{}", nanoid::nanoid!()));
+    fs::create_dir_all(&dir).unwrap();
+    let target = dir.join("verge-mihomo");
+    let (staged, backup) = sibling_paths(&target).unwrap();
+    fs::write(&target, "old core").unwrap();
+    fs::write(&staged, "new core").unwrap();
+
+    swap(&staged, &target, &backup).unwrap();
+    assert_eq!(fs::read_to_string(&target).unwrap(), "new core");
+    assert_eq!(fs::read_to_string(&backup).unwrap(), "old core");
+    assert!(!staged.exists());
+
+    restore(&target, &backup).unwrap();
+    assert_eq!(fs::read_to_string(&target).unwrap(), "old core");
+    assert!(restore(&target, &backup).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_fetch_archive() {
+    use warp::Filter;
+
+    let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    let core = warp::path("core.gz").map(|| "test");
+    let checksum = warp::path("core.gz.sha256").map(move || format!("{hash}  core.gz"));
+    let bad = warp::path("bad.gz").map(|| "tset");
+    let bad_checksum = warp::path("bad.gz.sha256").map(move || format!("{hash}  bad.gz"));
+    let chunked = warp::path("chunked").map(|| {
+        let (mut sender, body) = warp::hyper::Body::channel();
+        tokio::spawn(async move {
+            for chunk in ["test", "test"] {
+                let _ = sender.send_data(chunk.into()).await;
+            }
+        });
+        warp::http::Response::new(body)
+    });
+    let routes = checksum.or(core).or(bad_checksum).or(bad).or(chunked);
+    let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let client = Client::new();
+    let source = |name: &str, sha256: Option<&str>| UpdateSource {
+        url: format!("http://{addr}/{name}"),
+        sha256: sha256.map(|s| s.into()),
+        pubkey: None,
+    };
+
+    let archive = fetch_archive(&client, &source("core.gz", None))
+        .await
+        .unwrap();
+    assert_eq!(archive, b"test");
+    assert!(fetch_archive(&client, &source("core.gz", Some(hash)))
+        .await
+        .is_ok());
+    assert!(fetch_archive(&client, &source("bad.gz", None))
+        .await
+        .is_err());
+    // not found
+    assert!(fetch_archive(&client, &source("missing.gz", Some(hash)))
+        .await
+        .is_err());
+    // the signature is required when the key is set
+    let mut signed = source("core.gz", None);
+    signed.pubkey = Some("RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3".into());
+    assert!(fetch_archive(&client, &signed).await.is_err());
+
+    // larger than the limit, with or without the content length
+    let url = format!("http://{addr}/core.gz");
+    assert_eq!(fetch(&client, &url, 4).await.unwrap(), b"test");
+    assert!(fetch(&client, &url, 3).await.is_err());
+    let url = format!("http://{addr}/chunked");
+    assert_eq!(fetch(&client, &url, 8).await.unwrap(), b"testtest");
+    assert!(fetch(&client, &url, 7).await.is_err());
+}
@@ -0,0 +1,223 @@
+use super::{use_key_case, ChainSupport};
+use crate::core::binary::CoreVersion;
+use serde_yaml::{Mapping, Value};
+
+/// 内建的增强处理
+/// 直接修改 `Mapping`，不需要像 `use_script` 那样经过 json 和 js 引擎
+pub trait Transform {
+    /// used in the log
+    fn uid(&self) -> &'static str;
+
+    fn transform(&self, config: Mapping) -> Mapping;
+}
+
+/// meta 的一些处理
+/// meta 不支持 `script` 模式，改成 `rule`
+pub struct MetaGuard;
+
+impl Transform for MetaGuard {
+    fn uid(&self) -> &'static str {
+        "verge_meta_guard"
+    }
+
+    fn transform(&self, mut config: Mapping) -> Mapping {
+        if config.get("mode").and_then(Value::as_str) == Some("script") {
+            config.insert("mode".into(), "rule".into());
+        }
+        config
+    }
+}
+
+/// meta 1.13.2 alpn string 转 数组
+pub struct HyAlpn;
+
+impl Transform for HyAlpn {
+    fn uid(&self) -> &'static str {
+        "verge_hy_alpn"
+    }
+
+    fn transform(&self, mut config: Mapping) -> Mapping {
+        let proxies = match config.get_mut("proxies") {
+            Some(Value::Sequence(proxies)) => proxies,
+            _ => return config,
+        };
+
+        for proxy in proxies.iter_mut() {
+            let proxy = match proxy.as_mapping_mut() {
+                Some(proxy) => proxy,
+                None => continue,
+            };
+            if proxy.get("type").and_then(Value::as_str) != Some("hysteria") {
+                continue;
+            }
+            let alpn = match proxy.get("alpn") {
+                Some(Value::String(alpn)) => alpn.clone(),
+                _ => continue,
+            };
+            proxy.insert("alpn".into(), vec![alpn].into());
+        }
+        config
+    }
+}
+
+/// 内建支持的一些处理
+pub fn builtin() -> Vec<(ChainSupport, Box<dyn Transform>)> {
+    vec![
+        (
+            ChainSupport::ClashMeta,
+            Box::new(HyAlpn) as Box<dyn Transform>,
+        ),
+        (
+            ChainSupport::ClashMeta,
+            Box::new(MetaGuard) as Box<dyn Transform>,
+        ),
+        (
+            ChainSupport::ClashMetaAlpha,
+            Box::new(HyAlpn) as Box<dyn Transform>,
+        ),
+        (
+            ChainSupport::ClashMetaAlpha,
+            Box::new(MetaGuard) as Box<dyn Transform>,
+        ),
+    ]
+}
+
+/// 跑所有支持当前内核的内建处理
+pub fn use_builtin(config: Mapping, version: Option<&CoreVersion>, strict: bool) -> Mapping {
+    let transforms = builtin()
+        .into_iter()
+        .filter(|(s, _)| s.is_support(version))
+        .map(|(_, t)| t)
+        .collect::<Vec<_>>();
+
+    if transforms.is_empty() {
+        return config;
+    }
+
+    // the js builtin scripts used to lowercase the keys, keep the same key handling
+    let config = use_key_case(config, strict);
+    transforms.into_iter().fold(config, |config, item| {
+        log::debug!(target: "app", "run builtin enhancement {}", item.uid());
+        item.transform(config)
+    })
+}
+
+#[test]
+fn test_builtin() {
+    let config = r#"
+    Mode: script
+    proxies:
+      - name: hy
+        type: hysteria
+        alpn: h3
+      - name: hy-list
+        type: hysteria
+        alpn:
+          - h3
+      - name: ss
+        type: ss
+        alpn: h3
+  "#;
+
+    let config = serde_yaml::from_str::<Mapping>(config).unwrap();
+    let meta = CoreVersion::from_sidecar("verge-mihomo");
+    let config = use_builtin(config, meta.as_ref(), false);
+
+    assert_eq!(config.get("mode"), Some(&Value::from("rule")));
+
+    let proxies = config.get("proxies").unwrap().as_sequence().unwrap();
+    let alpn = |i: usize| proxies[i].get("alpn").unwrap().clone();
+    assert_eq!(alpn(0), Value::from(vec!["h3"]));
+    assert_eq!(alpn(1), Value::from(vec!["h3"]));
+    assert_eq!(alpn(2), Value::from("h3"));
+
+    let clash = Mapping::from_iter([("Mode".into(), "script".into())]);
+    let clash = use_builtin(clash, CoreVersion::from_sidecar("clash").as_ref(), false);
+    assert_eq!(clash.get("Mode"), Some(&Value::from("script")));
+}
+
+#[cfg(test)]
+const META_GUARD_JS: &str = r#"function main(config) {
+  if (config.mode === "script") {
+    config.mode = "rule";
+  }
+  return config;
+}"#;
+#[cfg(test)]
+const HY_ALPN_JS: &str = r#"function main(config) {
+  if (Array.isArray(config.proxies)) {
+    config.proxies.forEach((p, i) => {
+      if (p.type === "hysteria" && typeof p.alpn === "string") {
+        config.proxies[i].alpn = [p.alpn];
+      }
+    });
+  }
+  return config;
+}"#;
+
+/// a profile with `count` proxies, half of them are hysteria
+#[cfg(test)]
+fn profile_with_proxies(count: usize) -> Mapping {
+    let proxies = (0..count)
+        .map(|i| {
+            let mut proxy = Mapping::new();
+            proxy.insert("name".into(), format!("proxy-{i}").into());
+            let itype = if i % 2 == 0 { "hysteria" } else { "vmess" };
+            proxy.insert("type".into(), itype.into());
+            proxy.insert("server".into(), format!("{i}.example.com").into());
+            proxy.insert("port".into(), 443.into());
+            proxy.insert("alpn".into(), "h3".into());
+            Value::from(proxy)
+        })
+        .collect::<Vec<_>>();
+    let mut config = Mapping::new();
+    config.insert("mode".into(), "script".into());
+    config.insert("proxies".into(), proxies.into());
+    config
+}
+
+#[cfg(test)]
+fn use_builtin_scripts(config: Mapping) -> Mapping {
+    let (config, _) = super::use_script(HY_ALPN_JS.into(), config, false).unwrap();
+    let (config, _) = super::use_script(META_GUARD_JS.into(), config, false).unwrap();
+    config
+}
+
+/// the native transforms give the same result as the old js builtin scripts
+#[test]
+fn test_builtin_same_as_script() {
+    let config = profile_with_proxies(100);
+    let core = CoreVersion::from_sidecar("verge-mihomo");
+
+    let native = use_builtin(config.clone(), core.as_ref(), false);
+    assert_eq!(native, use_builtin_scripts(config));
+}
+
+/// the generation time of a large profile, run with
+/// `cargo test --release bench_builtin -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn bench_builtin() {
+    use std::time::Instant;
+
+    let core = CoreVersion::from_sidecar("verge-mihomo");
+    let times = 5;
+
+    for count in [1000, 5000, 10000] {
+        let config = profile_with_proxies(count);
+
+        let start = Instant::now();
+        for _ in 0..times {
+            use_builtin(config.clone(), core.as_ref(), false);
+        }
+        let native = start.elapsed() / times;
+
+        let start = Instant::now();
+        for _ in 0..times {
+            use_builtin_scripts(config.clone());
+        }
+        let script = start.elapsed() / times;
+
+        println!("{count} proxies, native: {native:?}, script: {script:?}");
+    }
+}
@@ -1,6 +0,0 @@
-function main(config) {
-  if (config.mode === "script") {
-    config.mode = "rule";
-  }
-  return config;
-}
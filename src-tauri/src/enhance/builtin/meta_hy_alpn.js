@@ -1,10 +0,0 @@
-function main(config) {
-  if (Array.isArray(config.proxies)) {
-    config.proxies.forEach((p, i) => {
-      if (p.type === "hysteria" && typeof p.alpn === "string") {
-        config.proxies[i].alpn = [p.alpn];
-      }
-    });
-  }
-  return config;
-}
@@ -1,5 +1,6 @@
 use crate::{
     config::PrfItem,
+    core::binary::{CoreKind, CoreVersion},
     utils::{dirs, help},
 };
 use serde_yaml::Mapping;
@@ -50,50 +51,16 @@ impl From<&PrfItem> for Option<ChainItem> {
     }
 }
 
-impl ChainItem {
-    /// 内建支持一些脚本
-    pub fn builtin() -> Vec<(ChainSupport, ChainItem)> {
-        // meta 的一些处理
-        let meta_guard =
-            ChainItem::to_script("verge_meta_guard", include_str!("./builtin/meta_guard.js"));
-
-        // meta 1.13.2 alpn string 转 数组
-        let hy_alpn =
-            ChainItem::to_script("verge_hy_alpn", include_str!("./builtin/meta_hy_alpn.js"));
-
-        // meta 的一些处理
-        let meta_guard_alpha =
-            ChainItem::to_script("verge_meta_guard", include_str!("./builtin/meta_guard.js"));
-
-        // meta 1.13.2 alpn string 转 数组
-        let hy_alpn_alpha =
-            ChainItem::to_script("verge_hy_alpn", include_str!("./builtin/meta_hy_alpn.js"));
-
-        vec![
-            (ChainSupport::ClashMeta, hy_alpn),
-            (ChainSupport::ClashMeta, meta_guard),
-            (ChainSupport::ClashMetaAlpha, hy_alpn_alpha),
-            (ChainSupport::ClashMetaAlpha, meta_guard_alpha),
-        ]
-    }
-
-    pub fn to_script<U: Into<String>, D: Into<String>>(uid: U, data: D) -> Self {
-        Self {
-            uid: uid.into(),
-            data: ChainType::Script(data.into()),
-        }
-    }
-}
-
 impl ChainSupport {
-    pub fn is_support(&self, core: Option<&String>) -> bool {
-        match core {
-            Some(core) => matches!(
-                (self, core.as_str()),
-                (ChainSupport::All, _)
-                    | (ChainSupport::Clash, "clash")
-                    | (ChainSupport::ClashMeta, "verge-mihomo")
-                    | (ChainSupport::ClashMetaAlpha, "verge-mihomo-alpha")
+    /// 根据检测到的内核版本判断，而不是 sidecar 的名字
+    pub fn is_support(&self, version: Option<&CoreVersion>) -> bool {
+        match version {
+            Some(version) => matches!(
+                (self, version.kind, version.alpha),
+                (ChainSupport::All, _, _)
+                    | (ChainSupport::Clash, CoreKind::Clash, _)
+                    | (ChainSupport::ClashMeta, CoreKind::Mihomo, false)
+                    | (ChainSupport::ClashMetaAlpha, CoreKind::Mihomo, true)
             ),
             None => true,
         }
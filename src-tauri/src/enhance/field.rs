@@ -27,6 +27,89 @@ pub const DEFAULT_FIELDS: [&str; 5] = [
     "rules",
 ];
 
+/// mihomo 支持的顶层配置键
+pub const MIHOMO_FIELDS: [&str; 69] = [
+    "port",
+    "socks-port",
+    "redir-port",
+    "tproxy-port",
+    "mixed-port",
+    "ss-config",
+    "vmess-config",
+    "tuic-server",
+    "tcptun-config",
+    "udptun-config",
+    "authentication",
+    "skip-auth-prefixes",
+    "lan-allowed-ips",
+    "lan-disallowed-ips",
+    "allow-lan",
+    "bind-address",
+    "mode",
+    "unified-delay",
+    "log-level",
+    "ipv6",
+    "external-controller",
+    "external-controller-tls",
+    "external-controller-unix",
+    "external-controller-pipe",
+    "external-controller-cors",
+    "external-ui",
+    "external-ui-url",
+    "external-ui-name",
+    "external-doh-server",
+    "secret",
+    "interface-name",
+    "routing-mark",
+    "geox-url",
+    "geodata-mode",
+    "geodata-loader",
+    "geosite-matcher",
+    "geo-auto-update",
+    "geo-update-interval",
+    "tcp-concurrent",
+    "find-process-mode",
+    "global-client-fingerprint",
+    "global-ua",
+    "etag-support",
+    "keep-alive-interval",
+    "keep-alive-idle",
+    "disable-keep-alive",
+    "inbound-tfo",
+    "inbound-mptcp",
+    "profile",
+    "tls",
+    "hosts",
+    "use-hosts",
+    "use-system-hosts",
+    "dns",
+    "ntp",
+    "tun",
+    "sniffer",
+    "sniffing",
+    "iptables",
+    "experimental",
+    "listeners",
+    "clash-for-android",
+    "proxies",
+    "proxy-providers",
+    "proxy-groups",
+    "rules",
+    "sub-rules",
+    "rule-providers",
+    "tunnels",
+];
+
+/// clash verge 的 merge 专用的键
+pub const MERGE_FIELDS: [&str; 6] = [
+    "prepend-rules",
+    "append-rules",
+    "prepend-proxies",
+    "append-proxies",
+    "prepend-proxy-groups",
+    "append-proxy-groups",
+];
+
 pub fn use_filter(config: Mapping, filter: &Vec<String>) -> Mapping {
     let mut ret = Mapping::new();
 
@@ -53,6 +136,44 @@ pub fn use_lowercase(config: Mapping) -> Mapping {
     ret
 }
 
+/// 严格模式
+/// 只把忽略大小写后能匹配已知键的键规范化，其余的键保持原样并打印警告
+pub fn use_strict_keys(config: Mapping) -> Mapping {
+    let mut ret = Mapping::new();
+
+    for (key, value) in config.into_iter() {
+        match key.as_str() {
+            Some(key_str) => match normalize_key(key_str) {
+                Some(known) => {
+                    ret.insert(Value::from(known), value);
+                }
+                None => {
+                    log::warn!(target: "app", "unknown config key `{key_str}`");
+                    ret.insert(key, value);
+                }
+            },
+            None => log::warn!(target: "app", "ignore the non-string config key `{key:?}`"),
+        }
+    }
+    ret
+}
+
+/// 根据是否开启严格模式处理键的大小写
+pub fn use_key_case(config: Mapping, strict: bool) -> Mapping {
+    match strict {
+        true => use_strict_keys(config),
+        false => use_lowercase(config),
+    }
+}
+
+/// find the known key which matches the key case-insensitively
+fn normalize_key(key: &str) -> Option<&'static str> {
+    MIHOMO_FIELDS
+        .into_iter()
+        .chain(MERGE_FIELDS)
+        .find(|known| known.eq_ignore_ascii_case(key))
+}
+
 pub fn use_sort(config: Mapping) -> Mapping {
     let mut ret = Mapping::new();
     HANDLE_FIELDS.into_iter().for_each(|key| {
@@ -64,14 +185,13 @@ pub fn use_sort(config: Mapping) -> Mapping {
 
     let supported_keys: HashSet<&str> = HANDLE_FIELDS.into_iter().chain(DEFAULT_FIELDS).collect();
 
-    let config_keys: HashSet<&str> = config.keys().filter_map(|e| e.as_str()).collect();
-
-    config_keys.difference(&supported_keys).for_each(|&key| {
-        let key = Value::from(key);
-        if let Some(value) = config.get(&key) {
-            ret.insert(key, value.clone());
-        }
-    });
+    // the unknown keys keep the original order
+    config
+        .iter()
+        .filter(|(key, _)| key.as_str().map_or(false, |k| !supported_keys.contains(k)))
+        .for_each(|(key, value)| {
+            ret.insert(key.clone(), value.clone());
+        });
     DEFAULT_FIELDS.into_iter().for_each(|key| {
         let key = Value::from(key);
         if let Some(value) = config.get(&key) {
@@ -82,14 +202,79 @@ pub fn use_sort(config: Mapping) -> Mapping {
     ret
 }
 
-pub fn use_keys(config: &Mapping) -> Vec<String> {
+pub fn use_keys(config: &Mapping, strict: bool) -> Vec<String> {
     config
         .iter()
         .filter_map(|(key, _)| key.as_str())
-        .map(|s| {
-            let mut s = s.to_string();
-            s.make_ascii_lowercase();
-            s
+        .map(|s| match strict {
+            true => normalize_key(s).unwrap_or(s).to_string(),
+            false => {
+                let mut s = s.to_string();
+                s.make_ascii_lowercase();
+                s
+            }
         })
         .collect()
 }
+
+#[test]
+fn test_sort() {
+    let config = r"
+    rules: []
+    sniffer: {}
+    mode: rule
+    hosts: {}
+    proxies: []
+    geodata-mode: true
+    mixed-port: 7890
+  ";
+
+    let config = serde_yaml::from_str::<Mapping>(config).unwrap();
+    let keys = use_sort(config)
+        .keys()
+        .filter_map(|key| key.as_str().map(|k| k.to_string()))
+        .collect::<Vec<String>>();
+
+    assert_eq!(
+        keys,
+        vec!["mode", "mixed-port", "sniffer", "hosts", "geodata-mode", "proxies", "rules"]
+    );
+}
+
+#[test]
+fn test_strict_keys() {
+    let config = r"
+    Mixed-Port: 7890
+    MODE: rule
+    Prepend-Rules: []
+    x-Anchors: {}
+    proxies: []
+  ";
+
+    let config = serde_yaml::from_str::<Mapping>(config).unwrap();
+    assert_eq!(
+        use_keys(&config, true),
+        vec![
+            "mixed-port",
+            "mode",
+            "prepend-rules",
+            "x-Anchors",
+            "proxies"
+        ]
+    );
+
+    let keys = use_strict_keys(config)
+        .keys()
+        .filter_map(|key| key.as_str().map(|k| k.to_string()))
+        .collect::<Vec<String>>();
+    assert_eq!(
+        keys,
+        vec![
+            "mixed-port",
+            "mode",
+            "prepend-rules",
+            "x-Anchors",
+            "proxies"
+        ]
+    );
+}
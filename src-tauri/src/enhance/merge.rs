@@ -1,15 +1,6 @@
-use super::{use_filter, use_lowercase};
+use super::{use_filter, use_key_case, MERGE_FIELDS};
 use serde_yaml::{self, Mapping, Sequence, Value};
 
-const MERGE_FIELDS: [&str; 6] = [
-    "prepend-rules",
-    "append-rules",
-    "prepend-proxies",
-    "append-proxies",
-    "prepend-proxy-groups",
-    "append-proxy-groups",
-];
-
 fn deep_merge(a: &mut Value, b: &Value) {
     match (a, b) {
         (&mut Value::Mapping(ref mut a), Value::Mapping(b)) => {
@@ -21,9 +12,9 @@ fn deep_merge(a: &mut Value, b: &Value) {
     }
 }
 
-pub fn use_merge(merge: Mapping, config: Mapping) -> Mapping {
+pub fn use_merge(merge: Mapping, config: Mapping, strict: bool) -> Mapping {
     let mut config = Value::from(config);
-    let mut merge_without_append = use_lowercase(merge.clone());
+    let mut merge_without_append = use_key_case(merge.clone(), strict);
     for key in MERGE_FIELDS {
         merge_without_append.remove(key).unwrap_or_default();
     }
@@ -98,7 +89,7 @@ fn test_merge() -> anyhow::Result<()> {
     let merge = serde_yaml::from_str::<Mapping>(merge)?;
     let config = serde_yaml::from_str::<Mapping>(config)?;
 
-    let result = serde_yaml::to_string(&use_merge(merge, config))?;
+    let result = serde_yaml::to_string(&use_merge(merge, config, false))?;
 
     println!("{result}");
 
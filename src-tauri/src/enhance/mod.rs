@@ -1,15 +1,18 @@
+mod builtin;
 mod chain;
 pub mod field;
 mod merge;
 mod script;
 mod tun;
 
+use self::builtin::*;
 use self::chain::*;
 use self::field::*;
 use self::merge::*;
 use self::script::*;
 use self::tun::*;
 use crate::config::Config;
+use crate::core::binary::CoreBinary;
 use crate::utils::dirs::app_home_dir;
 use anyhow::bail;
 use anyhow::Result;
@@ -78,51 +81,77 @@ pub fn generate_rule_providers(mut config: Mapping) -> Mapping {
 /// Enhance mode
 /// 返回最终订阅、该订阅包含的键、和script执行的结果
 pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
+    let keep_key_order = { Config::verge().latest().enable_keep_key_order };
+    // 从profiles里拿东西
+    let current = { Config::profiles().latest().current_mapping() };
+    let (mut config, mut exists_keys, result_map) = enhance_mapping(current.unwrap_or_default());
+
+    let enable_tun = Config::clash().latest().get_enable_tun();
+    config = use_tun(config, enable_tun);
+    if !keep_key_order.unwrap_or(false) {
+        config = use_sort(config);
+    }
+    config = generate_rule_providers(config);
+
+    let mut exists_set = HashSet::new();
+    exists_set.extend(exists_keys);
+    exists_keys = exists_set.into_iter().collect();
+
+    (config, exists_keys, result_map)
+}
+
+/// 用指定的订阅生成内核实例的配置，tun 和端口由实例自己处理
+pub fn enhance_profile(uid: &str) -> Result<Mapping> {
+    let keep_key_order = { Config::verge().latest().enable_keep_key_order };
+    let mapping = { Config::profiles().latest().mapping_of(uid)? };
+    let (mut config, _, _) = enhance_mapping(mapping);
+    if !keep_key_order.unwrap_or(false) {
+        config = use_sort(config);
+    }
+    Ok(config)
+}
+
+/// 订阅经过 chain、verge 的 clash 配置和内建处理
+fn enhance_mapping(mut config: Mapping) -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
     // config.yaml 的订阅
     let clash_config = { Config::clash().latest().0.clone() };
 
-    let (clash_core, enable_builtin) = {
+    let (enable_builtin, strict) = {
         let verge = Config::verge();
         let verge = verge.latest();
         (
-            verge.clash_core.clone(),
             verge.enable_builtin_enhanced.unwrap_or(true),
+            verge.enable_strict_key_case.unwrap_or(false),
         )
     };
-    // 从profiles里拿东西
-    let (mut config, chain) = {
+    let chain = {
         let profiles = Config::profiles();
         let profiles = profiles.latest();
 
-        let current = profiles.current_mapping().unwrap_or_default();
-
-        let chain = match profiles.chain.as_ref() {
+        match profiles.chain.as_ref() {
             Some(chain) => chain
                 .iter()
                 .filter_map(|uid| profiles.get_item(uid).ok())
                 .filter_map(<Option<ChainItem>>::from)
                 .collect::<Vec<ChainItem>>(),
             None => vec![],
-        };
-
-        (current, chain)
+        }
     };
-
     let mut result_map = HashMap::new(); // 保存脚本日志
-    let mut exists_keys = use_keys(&config); // 保存出现过的keys
+    let mut exists_keys = use_keys(&config, strict); // 保存出现过的keys
 
     // 处理用户的 profile
     chain.into_iter().for_each(|item| match item.data {
         ChainType::Merge(merge) => {
-            exists_keys.extend(use_keys(&merge));
-            config = use_merge(merge, config.to_owned());
+            exists_keys.extend(use_keys(&merge, strict));
+            config = use_merge(merge, config.to_owned(), strict);
         }
         ChainType::Script(script) => {
             let mut logs = vec![];
 
-            match use_script(script, config.to_owned()) {
+            match use_script(script, config.to_owned(), strict) {
                 Ok((res_config, res_logs)) => {
-                    exists_keys.extend(use_keys(&res_config));
+                    exists_keys.extend(use_keys(&res_config, strict));
                     config = res_config;
                     logs.extend(res_logs);
                 }
@@ -142,40 +171,24 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
         config.insert(key, value);
     }
 
-    // 内建脚本最后跑
+    // 内建处理最后跑
     if enable_builtin {
-        ChainItem::builtin()
-            .into_iter()
-            .filter(|(s, _)| s.is_support(clash_core.as_ref()))
-            .map(|(_, c)| c)
-            .for_each(|item| {
-                log::debug!(target: "app", "run builtin script {}", item.uid);
-                if let ChainType::Script(script) = item.data {
-                    match use_script(script, config.to_owned()) {
-                        Ok((res_config, _)) => {
-                            config = res_config;
-                        }
-                        Err(err) => {
-                            log::error!(target: "app", "builtin script error `{err}`");
-                        }
-                    }
-                }
-            });
+        let version = CoreBinary::current_version();
+        config = use_builtin(config, version.as_ref(), strict);
     }
 
-    let enable_tun = Config::clash().latest().get_enable_tun();
-    config = use_tun(config, enable_tun);
-    config = use_sort(config);
-    config = generate_rule_providers(config);
-
-    let mut exists_set = HashSet::new();
-    exists_set.extend(exists_keys);
-    exists_keys = exists_set.into_iter().collect();
-
     (config, exists_keys, result_map)
 }
 
 pub fn get_pre_merge_result(modified_chain_id: String) -> Result<MergeResult> {
+    let (keep_key_order, strict) = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        (
+            verge.enable_keep_key_order.unwrap_or(false),
+            verge.enable_strict_key_case.unwrap_or(false),
+        )
+    };
     let profiles = Config::profiles().latest().clone();
     let mut config = profiles.current_mapping().unwrap().clone();
     // let mut modified_chain_is_running = false;
@@ -203,12 +216,12 @@ pub fn get_pre_merge_result(modified_chain_id: String) -> Result<MergeResult> {
     chain.into_iter().for_each(|item| match item.data {
         ChainType::Merge(merge) => {
             // exists_keys.extend(use_keys(&merge));
-            config = use_merge(merge, config.to_owned());
+            config = use_merge(merge, config.to_owned(), strict);
         }
         ChainType::Script(script) => {
             let mut logs = vec![];
 
-            match use_script(script, config.to_owned()) {
+            match use_script(script, config.to_owned(), strict) {
                 Ok((res_config, res_logs)) => {
                     // exists_keys.extend(use_keys(&res_config));
                     config = res_config;
@@ -226,7 +239,9 @@ pub fn get_pre_merge_result(modified_chain_id: String) -> Result<MergeResult> {
     });
 
     // 排序
-    config = use_sort(config);
+    if !keep_key_order {
+        config = use_sort(config);
+    }
 
     Ok(MergeResult {
         config,
@@ -244,8 +259,13 @@ pub fn test_merge_chain(modified_chain_id: String, content: String) -> Result<Me
         logs: _,
     } = get_pre_merge_result(modified_chain_id.clone())?;
 
+    let strict = Config::verge()
+        .latest()
+        .enable_strict_key_case
+        .unwrap_or(false);
+
     let mut result_map = HashMap::new(); // 保存脚本日志
-    let mut exists_keys = use_keys(&config); // 保存出现过的keys
+    let mut exists_keys = use_keys(&config, strict); // 保存出现过的keys
 
     let profile_item = profiles.get_item(&modified_chain_id)?;
     let chain_type = profile_item.itype.as_ref().unwrap().as_str();
@@ -256,13 +276,13 @@ pub fn test_merge_chain(modified_chain_id: String, content: String) -> Result<Me
                 .as_mapping()
                 .unwrap()
                 .clone();
-            config = use_merge(yaml_content, config.to_owned());
+            config = use_merge(yaml_content, config.to_owned(), strict);
         }
         "script" => {
             let mut logs = vec![];
-            match use_script(content, config.to_owned()) {
+            match use_script(content, config.to_owned(), strict) {
                 Ok((res_config, res_logs)) => {
-                    exists_keys.extend(use_keys(&res_config));
+                    exists_keys.extend(use_keys(&res_config, strict));
                     config = res_config;
                     logs.extend(res_logs);
                 }
@@ -280,32 +300,18 @@ pub fn test_merge_chain(modified_chain_id: String, content: String) -> Result<Me
     };
 
     if should_build_final_config {
-        // 内建脚本最后跑
-        let (clash_core, enable_builtin) = {
+        // 内建处理最后跑
+        let (enable_builtin, keep_key_order) = {
             let verge = Config::verge();
             let verge = verge.latest();
             (
-                verge.clash_core.clone(),
                 verge.enable_builtin_enhanced.unwrap_or(true),
+                verge.enable_keep_key_order.unwrap_or(false),
             )
         };
         if enable_builtin {
-            ChainItem::builtin()
-                .into_iter()
-                .filter(|(s, _)| s.is_support(clash_core.as_ref()))
-                .map(|(_, c)| c)
-                .for_each(|item| {
-                    if let ChainType::Script(script) = item.data {
-                        match use_script(script, config.to_owned()) {
-                            Ok((res_config, _)) => {
-                                config = res_config;
-                            }
-                            Err(err) => {
-                                log::error!(target: "app", "builtin script error `{err}`");
-                            }
-                        }
-                    }
-                });
+            let version = CoreBinary::current_version();
+            config = use_builtin(config, version.as_ref(), strict);
         }
 
         //合并 verge 接管的配置
@@ -316,7 +322,9 @@ pub fn test_merge_chain(modified_chain_id: String, content: String) -> Result<Me
 
         let enable_tun = Config::clash().latest().get_enable_tun();
         config = use_tun(config, enable_tun);
-        config = use_sort(config);
+        if !keep_key_order {
+            config = use_sort(config);
+        }
         config = generate_rule_providers(config);
     }
 
@@ -1,10 +1,14 @@
 use crate::enhance::LogMessage;
 
-use super::use_lowercase;
+use super::use_key_case;
 use anyhow::{Error, Result};
 use serde_yaml::Mapping;
 
-pub fn use_script(script: String, config: Mapping) -> Result<(Mapping, Vec<LogMessage>)> {
+pub fn use_script(
+    script: String,
+    config: Mapping,
+    strict: bool,
+) -> Result<(Mapping, Vec<LogMessage>)> {
     use boa_engine::{native_function::NativeFunction, Context, JsValue, Source};
     use std::sync::{Arc, Mutex};
     let mut context = Context::default();
@@ -42,7 +46,7 @@ pub fn use_script(script: String, config: Mapping) -> Result<(Mapping, Vec<LogMe
       });"#,
     ));
 
-    let config = use_lowercase(config.clone());
+    let config = use_key_case(config.clone(), strict);
     let config_str = serde_json::to_string(&config)?;
 
     let code = format!(
@@ -68,7 +72,7 @@ pub fn use_script(script: String, config: Mapping) -> Result<(Mapping, Vec<LogMe
         let res: Result<Mapping, Error> = Ok(serde_json::from_str::<Mapping>(result.as_str())?);
         let mut out = outputs.lock().unwrap();
         match res {
-            Ok(config) => Ok((use_lowercase(config), out.to_vec())),
+            Ok(config) => Ok((use_key_case(config, strict), out.to_vec())),
             Err(err) => {
                 out.push(LogMessage {
                     method: "error".into(),
@@ -107,7 +111,7 @@ fn test_script() {
   "#;
 
     let config = serde_yaml::from_str(config).unwrap();
-    let (config, results) = use_script(script.into(), config).unwrap();
+    let (config, results) = use_script(script.into(), config, false).unwrap();
 
     let config_str = serde_yaml::to_string(&config).unwrap();
 
@@ -35,6 +35,7 @@ pub fn open_or_close_dashboard() {
 
 // 重启clash
 pub fn restart_clash_core() {
+    supervisor::Supervisor::global().reset();
     tauri::async_runtime::spawn(async {
         match CoreManager::global().run_core().await {
             Ok(_) => {
@@ -309,58 +310,61 @@ pub async fn patch_clash(patch: Mapping) -> Result<()> {
     Config::clash()
         .draft()
         .patch_and_merge_config(patch.clone());
-    let mut generate_runtime_config = false;
+    // 修改后的配置需要能按 mihomo 的格式解析
+    let checked = { Config::clash().draft().typed() };
+    match checked.and_then(|clash| clash.to_mapping()) {
+        Ok(config) => Config::clash().draft().0 = config,
+        Err(err) => {
+            Config::clash().discard();
+            bail!(err);
+        }
+    }
+    // 基本配置用一次 PATCH 修改，其他的按最小的代价重载
+    let mut plan = reload::ReloadPlan::new(&patch);
+    // PATCH 合并后的值，比如 tun 的子项
+    let merged = { Config::clash().latest().0.clone() };
+    for (key, value) in plan.patch.iter_mut() {
+        if let Some(merged) = merged.get(key) {
+            *value = merged.clone();
+        }
+    }
     let res = {
         let mut update_tun_failed = false;
-        for key in CLASH_BASIC_CONFIG {
-            if patch.get(key).is_some() {
-                if !generate_runtime_config {
-                    generate_runtime_config = true;
-                }
-                let mut mapping = Mapping::new();
-                let clash_config_mapping = { Config::clash().latest().0.clone() };
-                let value = clash_config_mapping.get(key).unwrap();
-
-                mapping.insert(key.into(), value.clone().into());
-                let _ = clash_api::patch_configs(&mapping).await?;
-
-                // handle tun config
-                if key == "tun" {
-                    let clash_basic_configs = clash_api::get_configs().await?;
-                    let tun_enable = value
-                        .as_mapping()
-                        .unwrap()
-                        .get("enable")
-                        .map_or(false, |val| val.as_bool().unwrap_or(false));
-                    let tun_enable_by_api = clash_basic_configs
-                        .tun
-                        .get("enable")
-                        .map_or(false, |val| val.as_bool().unwrap_or(false));
-                    if tun_enable == tun_enable_by_api {
-                        handle::Handle::update_systray_part()?;
-                    } else {
-                        update_tun_failed = true;
-                        break;
-                    }
-                }
-                // handle system proxy
-                if key == "mixed-port" {
-                    sysopt::Sysopt::global().update_sysproxy()?;
-                }
+        if !plan.patch.is_empty() {
+            clash_api::patch_configs(&plan.patch).await?;
+        }
+
+        // handle tun config
+        if let Some(tun) = plan.patch.get("tun") {
+            let clash_basic_configs = clash_api::get_configs().await?;
+            let tun_enable = tun
+                .as_mapping()
+                .and_then(|tun| tun.get("enable"))
+                .map_or(false, |val| val.as_bool().unwrap_or(false));
+            let tun_enable_by_api = clash_basic_configs
+                .tun
+                .get("enable")
+                .map_or(false, |val| val.as_bool().unwrap_or(false));
+            if tun_enable == tun_enable_by_api {
+                handle::Handle::update_systray_part()?;
+            } else {
+                update_tun_failed = true;
             }
         }
+        // handle system proxy
+        if !update_tun_failed && plan.patch.contains_key("mixed-port") {
+            sysopt::Sysopt::global().update_sysproxy()?;
+        }
 
         if update_tun_failed {
             <Result<(), Error>>::Err(anyhow!("Tun Device Or Resource Busy"))
         } else {
-            // 重新载入订阅
-            if patch.get("unified-delay").is_some() {
-                update_core_config().await?;
-            }
-            // 激活订阅
-            if patch.get("secret").is_some() || patch.get("external-controller").is_some() {
-                Config::generate()?;
-                CoreManager::global().run_core().await?;
+            match plan.action {
+                Some(reload::ReloadAction::Put) => update_core_config().await?,
+                Some(reload::ReloadAction::Restart) => {
+                    CoreManager::global().reload_controller().await?;
+                }
+                None => {}
             }
 
             if patch.get("mode").is_some() {
@@ -368,7 +372,7 @@ pub async fn patch_clash(patch: Mapping) -> Result<()> {
             }
 
             Config::runtime().latest().patch_config(patch);
-            if generate_runtime_config {
+            if !plan.patch.is_empty() {
                 // if the clash basic config changed, we need to sync the runtime configuration file now
                 Config::generate()?;
                 Config::generate_file(ConfigType::Run)?;
@@ -411,6 +415,71 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
     }
 }
 
+/// 添加自定义内核，检测版本并用当前订阅检查后保存
+pub async fn register_core(name: String, path: String) -> Result<IVergeCore> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        bail!("the name of the core is empty");
+    }
+    if binary::BUILTIN_CORES.contains(&name.as_str()) {
+        bail!("\"{name}\" is the name of a builtin core");
+    }
+    let path = std::path::PathBuf::from(path.trim());
+    if !path.is_file() {
+        bail!("the core binary \"{}\" does not exist", path.display());
+    }
+    let path = path.canonicalize()?;
+
+    let core = binary::CoreBinary::Custom {
+        name: name.clone(),
+        path: path.clone(),
+    };
+    let version = core.detect_version()?;
+    CoreManager::global().check_config_with(&core)?;
+
+    let item = IVergeCore {
+        name: name.clone(),
+        path: path.display().to_string(),
+        version: Some(version.version),
+    };
+    let mut cores = { Config::verge().latest().custom_cores.clone() }.unwrap_or_default();
+    cores.retain(|c| c.name != name);
+    cores.push(item.clone());
+
+    patch_verge(IVerge {
+        custom_cores: Some(cores),
+        ..IVerge::default()
+    })
+    .await?;
+    // the path of the current core may be changed
+    binary::CoreBinary::refresh_current_version();
+    Ok(item)
+}
+
+/// 删除自定义内核，正在使用的内核不能删除
+pub async fn unregister_core(name: String) -> Result<()> {
+    let (current, cores) = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        (verge.clash_core.clone(), verge.custom_cores.clone())
+    };
+    if current.as_deref() == Some(name.as_str()) {
+        bail!("\"{name}\" is in use, change the core first");
+    }
+    let mut cores = cores.unwrap_or_default();
+    let len = cores.len();
+    cores.retain(|c| c.name != name);
+    if cores.len() == len {
+        bail!("the core \"{name}\" is not found");
+    }
+
+    patch_verge(IVerge {
+        custom_cores: Some(cores),
+        ..IVerge::default()
+    })
+    .await
+}
+
 pub async fn resolve_config_settings(patch: IVerge) -> Result<()> {
     let auto_launch = patch.enable_auto_launch;
     let system_proxy = patch.enable_system_proxy;
@@ -452,6 +521,10 @@ pub async fn resolve_config_settings(patch: IVerge) -> Result<()> {
         hotkey::Hotkey::global().update(hotkeys)?;
     }
 
+    if patch.latency_test_interval.is_some() {
+        timer::Timer::global().refresh_latency_test()?;
+    }
+
     if language.is_some() {
         handle::Handle::update_systray()?;
     } else if system_proxy.is_some()
@@ -477,6 +550,31 @@ pub async fn resolve_config_settings(patch: IVerge) -> Result<()> {
 /// 更新某个profile
 /// 如果更新当前订阅就激活订阅
 pub async fn update_profile(uid: String, option: Option<PrfOption>) -> Result<()> {
+    if download_profile(uid, option).await? {
+        update_core_config().await?;
+    }
+
+    Ok(())
+}
+
+/// 导入订阅，重复的订阅会按 mode 更新已有的那一项
+/// 更新的是当前订阅时重新生成配置，返回订阅的 uid
+pub async fn import_profile(item: PrfItem, mode: DuplicateMode) -> Result<String> {
+    let (uid, current) = {
+        let profiles = Config::profiles();
+        let mut profiles = profiles.data();
+        let uid = profiles.append_item(item, mode)?;
+        (uid, profiles.get_current())
+    };
+    if current.as_ref() == Some(&uid) {
+        update_core_config().await?;
+    }
+    Ok(uid)
+}
+
+/// 下载远程订阅，不通知内核
+/// 返回是否需要重新生成配置
+pub async fn download_profile(uid: String, option: Option<PrfOption>) -> Result<bool> {
     let url_opt = {
         let profiles = Config::profiles();
         let profiles = profiles.latest();
@@ -506,11 +604,7 @@ pub async fn update_profile(uid: String, option: Option<PrfOption>) -> Result<()
         None => true,
     };
 
-    if should_update {
-        update_core_config().await?;
-    }
-
-    Ok(())
+    Ok(should_update)
 }
 
 /// 更新订阅
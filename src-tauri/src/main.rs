@@ -12,7 +12,7 @@ mod utils;
 
 use crate::{
     config::Config,
-    utils::{init, resolve, server},
+    utils::{cli, dirs, init, resolve, server},
 };
 use core::{verge_log::VergeLog, CoreManager};
 use std::{
@@ -21,7 +21,37 @@ use std::{
 };
 use tauri::api::dialog::{blocking::MessageDialogBuilder, MessageDialogButtons, MessageDialogKind};
 
+/// 不创建窗口和托盘，只运行内核
+const HEADLESS_ARG: &str = "--headless";
+
 fn main() -> std::io::Result<()> {
+    let headless = std::env::args().any(|arg| arg == HEADLESS_ARG);
+
+    // 命令行的子命令，交给运行中的 app 或者直接修改配置文件
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match cli::CliCommand::parse(&args) {
+        Ok(Some(command)) => {
+            #[cfg(windows)]
+            cli::attach_console();
+            crate::log_err!(dirs::init_portable_flag());
+            match cli::run(command) {
+                Ok(output) => println!("{output}"),
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        Ok(None) => {}
+        Err(err) => {
+            #[cfg(windows)]
+            cli::attach_console();
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    }
+
     // 单例检测
     if server::check_singleton().is_err() {
         log::info!("app exists");
@@ -54,6 +84,14 @@ fn main() -> std::io::Result<()> {
         };
 
         log::error!("panicked at {}:\n{}\n{}", location, payload, backtrace);
+        // 没有窗口，不弹对话框
+        if headless {
+            let task = std::thread::spawn(|| {
+                let _ = CoreManager::global().stop_core();
+            });
+            let _ = task.join();
+            std::process::exit(1);
+        }
         let limit_backtrace = backtrace.lines().take(10).collect::<Vec<_>>().join("\n");
         let log_file = VergeLog::global().get_log_file().unwrap_or("".to_string());
         let backtrace_in_dialog = format!(
@@ -76,6 +114,17 @@ fn main() -> std::io::Result<()> {
         }
     }));
 
+    let context = tauri::generate_context!();
+
+    if headless {
+        let package_info = context.package_info().clone();
+        let version = package_info.version.to_string();
+        dirs::PACKAGE_INFO.get_or_init(|| package_info);
+        tauri::async_runtime::block_on(resolve::resolve_headless(version));
+        resolve::resolve_reset();
+        return Ok(());
+    }
+
     #[allow(unused_mut)]
     let mut builder = tauri::Builder::default()
         // .system_tray(SystemTray::new())
@@ -134,14 +183,26 @@ fn main() -> std::io::Result<()> {
             cmds::get_portable_flag,
             // cmds::kill_sidecar,
             cmds::restart_sidecar,
+            cmds::get_core_status,
             cmds::grant_permission,
             // clash
             cmds::restart_clash,
             cmds::get_clash_configs,
             cmds::get_clash_info,
             cmds::get_clash_logs,
+            cmds::get_monitor_stats,
+            cmds::get_monitor_logs,
+            cmds::get_traffic_stats,
             cmds::patch_clash_config,
             cmds::change_clash_core,
+            cmds::get_core_list,
+            cmds::register_core,
+            cmds::unregister_core,
+            cmds::get_core_instances,
+            cmds::start_core_instance,
+            cmds::stop_core_instance,
+            cmds::update_core,
+            cmds::rollback_core,
             cmds::get_runtime_config,
             cmds::get_runtime_yaml,
             cmds::get_runtime_exists,
@@ -169,18 +230,31 @@ fn main() -> std::io::Result<()> {
             cmds::patch_profile,
             cmds::create_profile,
             cmds::import_profile,
+            cmds::find_profiles_by_url,
             cmds::reorder_profile,
             cmds::update_profile,
             cmds::delete_profile,
             cmds::read_profile_file,
             cmds::get_current_profile_rule_providers,
             cmds::save_profile_file,
+            cmds::validate_rule,
+            cmds::get_merge_rules,
+            cmds::add_merge_rule,
+            cmds::move_merge_rule,
+            cmds::delete_merge_rule,
             // service mode
             cmds::service::check_service,
             cmds::service::install_service,
             cmds::service::uninstall_service,
             // clash api
             cmds::clash_api_get_proxy_delay,
+            cmds::get_connections,
+            cmds::close_connections,
+            cmds::select_proxy,
+            cmds::restore_selected,
+            cmds::test_latency,
+            cmds::get_latency_ranking,
+            cmds::get_latency_history,
             // web dav
             cmds::update_webdav_info,
             cmds::create_and_upload_backup,
@@ -212,7 +286,7 @@ fn main() -> std::io::Result<()> {
     }
 
     let app = builder
-        .build(tauri::generate_context!())
+        .build(context)
         .expect("error while running tauri application");
 
     app.run(|app_handle, e| match e {
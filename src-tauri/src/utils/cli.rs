@@ -0,0 +1,343 @@
+use crate::{
+    cmds,
+    config::{Config, DuplicateMode, IProfiles, IVerge, PrfItem},
+    core::{supervisor::Supervisor, CoreManager},
+    feat,
+};
+use anyhow::{anyhow, bail, Result};
+use port_scanner::local_port_available;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+
+pub const USAGE: &str = "Usage: clash-verge <command>
+
+Commands:
+  profile list              list the profiles, `*` marks the current one
+  profile import <url>      import a remote profile, or update the same one
+  profile update [uid]      update the profile, the current one by default
+  profile select <uid>      use the profile
+  mode <rule|global|direct> change the clash mode
+  tun <on|off>              enable or disable the tun mode
+  sysproxy <on|off>         enable or disable the system proxy
+  core restart              restart the clash core
+  config show               print the runtime config
+  help                      print this message
+
+The commands are sent to the running app, or applied to the config files
+if the app is not running, and take effect on the next launch.";
+
+/// the subcommands, sent to the running app as json
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum CliCommand {
+    Help,
+    ProfileList,
+    ProfileImport {
+        url: String,
+    },
+    /// the current profile if none
+    ProfileUpdate {
+        uid: Option<String>,
+    },
+    ProfileSelect {
+        uid: String,
+    },
+    Mode {
+        mode: String,
+    },
+    Tun {
+        enable: bool,
+    },
+    Sysproxy {
+        enable: bool,
+    },
+    CoreRestart,
+    ConfigShow,
+}
+
+impl CliCommand {
+    /// none if the args are not a subcommand, like the scheme url or `--headless`
+    pub fn parse(args: &[String]) -> Result<Option<Self>> {
+        let args = args.iter().map(|arg| arg.as_str()).collect::<Vec<_>>();
+        let Some((&name, rest)) = args.split_first() else {
+            return Ok(None);
+        };
+
+        let command = match (name, rest) {
+            ("help" | "--help" | "-h", _) => Self::Help,
+            ("profile", ["list"]) => Self::ProfileList,
+            ("profile", ["import", url]) => Self::ProfileImport {
+                url: url.to_string(),
+            },
+            ("profile", ["update"]) => Self::ProfileUpdate { uid: None },
+            ("profile", ["update", uid]) => Self::ProfileUpdate {
+                uid: Some(uid.to_string()),
+            },
+            ("profile", ["select", uid]) => Self::ProfileSelect {
+                uid: uid.to_string(),
+            },
+            ("mode", [mode @ ("rule" | "global" | "direct")]) => Self::Mode {
+                mode: mode.to_string(),
+            },
+            ("tun", [switch]) => Self::Tun {
+                enable: parse_switch(switch)?,
+            },
+            ("sysproxy", [switch]) => Self::Sysproxy {
+                enable: parse_switch(switch)?,
+            },
+            ("core", ["restart"]) => Self::CoreRestart,
+            ("config", ["show"]) => Self::ConfigShow,
+            ("profile" | "mode" | "tun" | "sysproxy" | "core" | "config", _) => {
+                bail!("invalid arguments for `{name}`\n\n{USAGE}")
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+}
+
+fn parse_switch(switch: &str) -> Result<bool> {
+    match switch {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => bail!("expect `on` or `off`, found `{switch}`"),
+    }
+}
+
+/// the release build uses the windows subsystem and has no console,
+/// attach to the console of the terminal before printing
+#[cfg(windows)]
+pub fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // it fails if not started from a terminal, there is nowhere to print then
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+/// send the command to the running app over the singleton server,
+/// or apply it to the config files
+pub fn run(command: CliCommand) -> Result<String> {
+    if command == CliCommand::Help {
+        return Ok(USAGE.into());
+    }
+
+    let port = IVerge::get_singleton_port();
+    tauri::async_runtime::block_on(async move {
+        if local_port_available(port) {
+            return execute(command, false).await;
+        }
+
+        // 运行中的 app 需要 verge.yaml 里的 token
+        let token = { Config::verge().latest().control_token.clone() };
+        let response = reqwest::Client::new()
+            .post(format!("http://127.0.0.1:{port}/commands/cli"))
+            .bearer_auth(token.unwrap_or_default())
+            .json(&command)
+            .send()
+            .await?;
+        let success = response.status().is_success();
+        let text = response.text().await?;
+        match success {
+            true => Ok(text),
+            false => Err(anyhow!(text)),
+        }
+    })
+}
+
+/// run the command, `online` is true in the running app
+pub async fn execute(command: CliCommand, online: bool) -> Result<String> {
+    match command {
+        CliCommand::Help => Ok(USAGE.into()),
+        CliCommand::ProfileList => {
+            let profiles = Config::profiles();
+            let profiles = profiles.latest();
+            let current = profiles.get_current();
+            let items = profiles.get_items().cloned().unwrap_or_default();
+            let lines = items
+                .iter()
+                .filter(|item| matches!(item.itype.as_deref(), Some("remote" | "local")))
+                .map(|item| {
+                    let mark = if item.uid == current { "*" } else { " " };
+                    format!(
+                        "{mark} {}  {}  {}",
+                        item.uid.as_deref().unwrap_or_default(),
+                        item.itype.as_deref().unwrap_or_default(),
+                        item.name.as_deref().unwrap_or_default(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            Ok(lines.join("\n"))
+        }
+        CliCommand::ProfileImport { url } => {
+            let item = PrfItem::from_url(&url, None, None, None).await?;
+            // 已有的订阅会被更新，uid 不变
+            let uid = match online {
+                true => feat::import_profile(item, DuplicateMode::Merge).await?,
+                false => Config::profiles()
+                    .data()
+                    .append_item(item, DuplicateMode::Merge)?,
+            };
+            Ok(format!("imported {uid}"))
+        }
+        CliCommand::ProfileUpdate { uid } => {
+            let uid = match uid {
+                Some(uid) => uid,
+                None => Config::profiles()
+                    .latest()
+                    .get_current()
+                    .ok_or(anyhow!("no profile is selected"))?,
+            };
+            if online {
+                feat::update_profile(uid.clone(), None).await?;
+            } else {
+                feat::download_profile(uid.clone(), None).await?;
+            }
+            Ok(format!("updated {uid}"))
+        }
+        CliCommand::ProfileSelect { uid } => {
+            Config::profiles().latest().get_item(&uid)?;
+            let patch = IProfiles {
+                current: Some(uid.clone()),
+                ..IProfiles::default()
+            };
+            if online {
+                cmds::patch_profiles_config(patch)
+                    .await
+                    .map_err(|err| anyhow!(err))?;
+            } else {
+                let profiles = Config::profiles();
+                let mut profiles = profiles.data();
+                profiles.patch_config(patch)?;
+                profiles.save_file()?;
+            }
+            Ok(format!("selected {uid}"))
+        }
+        CliCommand::Mode { mode } => {
+            let mut patch = Mapping::new();
+            patch.insert("mode".into(), mode.clone().into());
+            patch_clash(patch, online).await?;
+            Ok(format!("mode: {mode}"))
+        }
+        CliCommand::Tun { enable } => {
+            let mut tun = Mapping::new();
+            tun.insert("enable".into(), enable.into());
+            let mut patch = Mapping::new();
+            patch.insert("tun".into(), tun.into());
+            patch_clash(patch, online).await?;
+            Ok(format!("tun: {}", switch_name(enable)))
+        }
+        CliCommand::Sysproxy { enable } => {
+            let patch = IVerge {
+                enable_system_proxy: Some(enable),
+                ..IVerge::default()
+            };
+            if online {
+                feat::patch_verge(patch).await?;
+            } else {
+                let verge = Config::verge();
+                let mut verge = verge.data();
+                verge.patch_config(patch);
+                verge.save_file()?;
+            }
+            Ok(format!("sysproxy: {}", switch_name(enable)))
+        }
+        CliCommand::CoreRestart => {
+            if !online {
+                bail!("the app is not running");
+            }
+            Supervisor::global().reset();
+            CoreManager::global().run_core().await?;
+            Ok("core restarted".into())
+        }
+        CliCommand::ConfigShow => {
+            // 没有运行时先生成一次
+            if !online {
+                Config::generate()?;
+            }
+            let runtime = Config::runtime();
+            let runtime = runtime.latest();
+            let config = runtime.config.clone().unwrap_or_default();
+            Ok(serde_yaml::to_string(&config)?)
+        }
+    }
+}
+
+/// the running core is patched, otherwise only `config.yaml` is saved
+async fn patch_clash(patch: Mapping, online: bool) -> Result<()> {
+    if online {
+        return feat::patch_clash(patch).await;
+    }
+    let clash = Config::clash();
+    let mut clash = clash.data();
+    clash.patch_and_merge_config(patch);
+    clash.save_config()
+}
+
+fn switch_name(enable: bool) -> &'static str {
+    match enable {
+        true => "on",
+        false => "off",
+    }
+}
+
+#[test]
+fn test_parse_command() {
+    let parse = |args: &str| {
+        let args = args
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        CliCommand::parse(&args)
+    };
+
+    assert_eq!(parse("").unwrap(), None);
+    assert_eq!(parse("--headless").unwrap(), None);
+    assert_eq!(
+        parse("clash://install-config?url=https://example.com").unwrap(),
+        None
+    );
+    assert_eq!(parse("help").unwrap(), Some(CliCommand::Help));
+    assert_eq!(
+        parse("profile list").unwrap(),
+        Some(CliCommand::ProfileList)
+    );
+    assert_eq!(
+        parse("profile update").unwrap(),
+        Some(CliCommand::ProfileUpdate { uid: None })
+    );
+    assert_eq!(
+        parse("profile select R1").unwrap(),
+        Some(CliCommand::ProfileSelect { uid: "R1".into() })
+    );
+    assert_eq!(
+        parse("mode global").unwrap(),
+        Some(CliCommand::Mode {
+            mode: "global".into()
+        })
+    );
+    assert_eq!(
+        parse("tun off").unwrap(),
+        Some(CliCommand::Tun { enable: false })
+    );
+    assert_eq!(
+        parse("core restart").unwrap(),
+        Some(CliCommand::CoreRestart)
+    );
+
+    assert!(parse("mode script").is_err());
+    assert!(parse("sysproxy yes").is_err());
+    assert!(parse("profile import").is_err());
+
+    let json = serde_json::to_string(&CliCommand::Tun { enable: true }).unwrap();
+    assert_eq!(json, r#"{"command":"tun","enable":true}"#);
+    assert_eq!(
+        serde_json::from_str::<CliCommand>(&json).unwrap(),
+        CliCommand::Tun { enable: true }
+    );
+}
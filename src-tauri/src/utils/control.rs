@@ -0,0 +1,391 @@
+//! the JSON control API on the singleton port, for the scripts and tools on this machine
+//!
+//! every request needs the header `Authorization: Bearer <control_token>`,
+//! the token is generated into `verge.yaml` on the first launch
+//!
+//! - `GET  /api/status` the core state, mode, tun, system proxy and the current profile
+//! - `GET  /api/profiles` the current uid and the profiles
+//! - `POST /api/profiles` `{"url": "https://.."}` import a remote profile, or update the same one
+//! - `POST /api/profiles/{uid}/update` update the profile
+//! - `PUT  /api/profiles/current` `{"uid": ".."}` use the profile
+//! - `PUT  /api/mode` `{"mode": "rule"}` rule | global | direct
+//! - `PUT  /api/tun` `{"enable": true}`
+//! - `PUT  /api/sysproxy` `{"enable": true}`
+//! - `POST /api/core/restart`
+//!
+//! the response is the json data, `{"message": ".."}` for the changes,
+//! or `{"error": ".."}` with a 4xx status for the bad requests, 500 if the change fails
+
+use super::{
+    cli::{self, CliCommand},
+    resolve::VERSION,
+};
+use crate::{
+    config::Config,
+    core::supervisor::{Supervisor, SupervisorStatus},
+};
+use anyhow::Result;
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use warp::{
+    http::StatusCode,
+    reply::{self, Response},
+    Filter, Rejection, Reply,
+};
+
+const MODES: [&str; 3] = ["rule", "global", "direct"];
+
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// the request is rejected by the other checks, with the reason
+#[derive(Debug)]
+pub struct Forbidden(pub String);
+
+impl warp::reject::Reject for Forbidden {}
+
+#[derive(Debug, Serialize)]
+struct Status {
+    version: Option<String>,
+    core: SupervisorStatus,
+    mode: String,
+    tun: bool,
+    system_proxy: bool,
+    current_profile: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProfileInfo {
+    uid: Option<String>,
+    #[serde(rename = "type")]
+    itype: Option<String>,
+    name: Option<String>,
+    url: Option<String>,
+    updated: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct Profiles {
+    current: Option<String>,
+    items: Vec<ProfileInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UrlBody {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UidBody {
+    uid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModeBody {
+    mode: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SwitchBody {
+    enable: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Message {
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// generate the token into `verge.yaml` if it's not set
+pub fn init_token() -> Result<()> {
+    let verge = Config::verge();
+    let mut verge = verge.data();
+    if verge.control_token.is_none() {
+        verge.control_token = Some(nanoid!(32));
+        verge.save_file()?;
+    }
+    Ok(())
+}
+
+/// the header must be `Bearer <token>`, an empty token never matches
+pub fn check_token(header: Option<&str>, token: Option<&str>) -> bool {
+    let given = header.and_then(|header| header.strip_prefix("Bearer "));
+    match (given, token) {
+        (Some(given), Some(token)) => token_eq(given, token),
+        _ => false,
+    }
+}
+
+/// compare all the bytes, the time doesn't depend on the common prefix
+pub fn token_eq(given: &str, token: &str) -> bool {
+    !token.is_empty()
+        && given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// reject the request without the control token
+pub fn authorized() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and_then(|header: Option<String>| async move {
+            let token = { Config::verge().latest().control_token.clone() };
+            match check_token(header.as_deref(), token.as_deref()) {
+                true => Ok(()),
+                false => Err(warp::reject::custom(Unauthorized)),
+            }
+        })
+        .untuple_one()
+}
+
+pub fn routes() -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    let status = warp::path!("status")
+        .and(warp::get())
+        .then(|| async { json_reply(Ok(status())) });
+
+    let profiles = warp::path!("profiles")
+        .and(warp::get())
+        .then(|| async { json_reply(Ok(profiles())) });
+
+    let import = warp::path!("profiles")
+        .and(warp::post())
+        .and(warp::body::json())
+        .then(|body: UrlBody| async move {
+            let url = reqwest::Url::parse(&body.url);
+            if !url.is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+                return error_reply(StatusCode::BAD_REQUEST, "invalid url");
+            }
+            execute(CliCommand::ProfileImport { url: body.url }).await
+        });
+
+    let update = warp::path!("profiles" / String / "update")
+        .and(warp::post())
+        .then(|uid: String| async move {
+            if let Err(reply) = check_profile(&uid) {
+                return reply;
+            }
+            execute(CliCommand::ProfileUpdate { uid: Some(uid) }).await
+        });
+
+    let select = warp::path!("profiles" / "current")
+        .and(warp::put())
+        .and(warp::body::json())
+        .then(|body: UidBody| async move {
+            if let Err(reply) = check_profile(&body.uid) {
+                return reply;
+            }
+            execute(CliCommand::ProfileSelect { uid: body.uid }).await
+        });
+
+    let mode = warp::path!("mode")
+        .and(warp::put())
+        .and(warp::body::json())
+        .then(|body: ModeBody| async move {
+            if !MODES.contains(&body.mode.as_str()) {
+                return error_reply(StatusCode::BAD_REQUEST, "invalid mode");
+            }
+            execute(CliCommand::Mode { mode: body.mode }).await
+        });
+
+    let tun = warp::path!("tun")
+        .and(warp::put())
+        .and(warp::body::json())
+        .then(|body: SwitchBody| {
+            execute(CliCommand::Tun {
+                enable: body.enable,
+            })
+        });
+
+    let sysproxy = warp::path!("sysproxy")
+        .and(warp::put())
+        .and(warp::body::json())
+        .then(|body: SwitchBody| {
+            execute(CliCommand::Sysproxy {
+                enable: body.enable,
+            })
+        });
+
+    let restart = warp::path!("core" / "restart")
+        .and(warp::post())
+        .then(|| execute(CliCommand::CoreRestart));
+
+    warp::path("api").and(authorized()).and(
+        status
+            .or(profiles)
+            .unify()
+            .or(import)
+            .unify()
+            .or(update)
+            .unify()
+            .or(select)
+            .unify()
+            .or(mode)
+            .unify()
+            .or(tun)
+            .unify()
+            .or(sysproxy)
+            .unify()
+            .or(restart)
+            .unify(),
+    )
+}
+
+/// 404 if the profile does not exist
+fn check_profile(uid: &String) -> Result<(), Response> {
+    match Config::profiles().latest().get_item(uid) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(error_reply(StatusCode::NOT_FOUND, &err.to_string())),
+    }
+}
+
+/// the changes are the same as the cli commands
+/// the requests are checked before, so a failure is an internal error
+async fn execute(command: CliCommand) -> Response {
+    let result = cli::execute(command, true).await;
+    json_reply(result.map(|message| Message { message }))
+}
+
+fn status() -> Status {
+    let (mode, tun) = {
+        let clash = Config::clash();
+        let clash = clash.latest();
+        (clash.get_mode(), clash.get_enable_tun())
+    };
+    let system_proxy = { Config::verge().latest().enable_system_proxy };
+    let current_profile = { Config::profiles().latest().get_current() };
+
+    Status {
+        version: VERSION.get().cloned(),
+        core: Supervisor::global().status(),
+        mode,
+        tun,
+        system_proxy: system_proxy.unwrap_or(false),
+        current_profile,
+    }
+}
+
+fn profiles() -> Profiles {
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    let items = profiles
+        .get_items()
+        .map(|items| {
+            items
+                .iter()
+                .filter(|item| matches!(item.itype.as_deref(), Some("remote" | "local")))
+                .map(|item| ProfileInfo {
+                    uid: item.uid.clone(),
+                    itype: item.itype.clone(),
+                    name: item.name.clone(),
+                    url: item.url.clone(),
+                    updated: item.updated,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Profiles {
+        current: profiles.get_current(),
+        items,
+    }
+}
+
+fn json_reply<T: Serialize>(result: Result<T>) -> Response {
+    match result {
+        Ok(data) => reply::json(&data).into_response(),
+        Err(err) => error_reply(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string()),
+    }
+}
+
+fn error_reply(status: StatusCode, error: &str) -> Response {
+    let body = ErrorBody {
+        error: error.to_string(),
+    };
+    reply::with_status(reply::json(&body), status).into_response()
+}
+
+/// turn the rejections into json errors
+pub async fn handle_rejection(err: Rejection) -> Result<Response, Infallible> {
+    let reply = if err.find::<Unauthorized>().is_some() {
+        error_reply(StatusCode::UNAUTHORIZED, "invalid control token")
+    } else if let Some(Forbidden(reason)) = err.find::<Forbidden>() {
+        error_reply(StatusCode::FORBIDDEN, reason)
+    } else if err.is_not_found() {
+        error_reply(StatusCode::NOT_FOUND, "not found")
+    } else if let Some(err) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        error_reply(StatusCode::BAD_REQUEST, &err.to_string())
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        error_reply(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+    } else {
+        error_reply(StatusCode::BAD_REQUEST, &format!("{err:?}"))
+    };
+    Ok(reply)
+}
+
+#[test]
+fn test_check_token() {
+    let token = Some("abcdef");
+    assert!(check_token(Some("Bearer abcdef"), token));
+    assert!(!check_token(Some("Bearer abcdeg"), token));
+    assert!(!check_token(Some("Bearer abc"), token));
+    assert!(!check_token(Some("abcdef"), token));
+    assert!(!check_token(None, token));
+    assert!(!check_token(Some("Bearer "), Some("")));
+    assert!(!check_token(Some("Bearer abcdef"), None));
+}
+
+#[tokio::test]
+async fn test_routes() {
+    Config::verge().data().control_token = Some("test-token".into());
+    let api = routes().recover(handle_rejection);
+
+    let cases = [
+        ("GET", "/api/status", None, "", StatusCode::UNAUTHORIZED),
+        (
+            "GET",
+            "/api/status",
+            Some("wrong"),
+            "",
+            StatusCode::UNAUTHORIZED,
+        ),
+        ("GET", "/api/status", Some("test-token"), "", StatusCode::OK),
+        (
+            "PUT",
+            "/api/mode",
+            Some("test-token"),
+            r#"{"mode":"tunnel"}"#,
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            "POST",
+            "/api/profiles",
+            Some("test-token"),
+            r#"{"url":"file:///a"}"#,
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            "PUT",
+            "/api/profiles/current",
+            Some("test-token"),
+            r#"{"uid":"missing"}"#,
+            StatusCode::NOT_FOUND,
+        ),
+    ];
+    for (method, path, token, body, expected) in cases {
+        let mut request = warp::test::request().method(method).path(path).body(body);
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {token}"));
+        }
+        let response = request.reply(&api).await;
+        assert_eq!(response.status(), expected, "{method} {path}");
+    }
+}
@@ -4,7 +4,7 @@ use once_cell::sync::OnceCell;
 use std::path::PathBuf;
 use tauri::{
     api::path::{data_dir, resource_dir},
-    Env,
+    Env, PackageInfo,
 };
 
 #[cfg(not(feature = "verge-dev"))]
@@ -14,9 +14,15 @@ pub static APP_ID: &str = "io.github.oomeow.clash-verge-self.dev";
 
 pub static PORTABLE_FLAG: OnceCell<bool> = OnceCell::new();
 
+/// the headless mode has no app handle, get the resource dir by the package info
+pub static PACKAGE_INFO: OnceCell<PackageInfo> = OnceCell::new();
+
 pub static CLASH_CONFIG: &str = "config.yaml";
 pub static VERGE_CONFIG: &str = "verge.yaml";
 pub static PROFILE_YAML: &str = "profiles.yaml";
+pub static TRAFFIC_JSON: &str = "traffic.json";
+pub static CORE_PID: &str = "core.pid";
+pub static SCHEME_TOKEN: &str = "scheme.token";
 
 /// init portable flag
 pub fn init_portable_flag() -> Result<()> {
@@ -57,13 +63,16 @@ pub fn app_home_dir() -> Result<PathBuf> {
 pub fn app_resources_dir() -> Result<PathBuf> {
     let handle = handle::Handle::global();
     let app_handle = handle.app_handle.lock();
-    if let Some(app_handle) = app_handle.as_ref() {
-        let res_dir = resource_dir(app_handle.package_info(), &Env::default())
-            .ok_or(anyhow::anyhow!("failed to get the resource dir"))?
-            .join("resources");
-        return Ok(res_dir);
+    let package_info = match app_handle.as_ref() {
+        Some(app_handle) => app_handle.package_info(),
+        None => PACKAGE_INFO
+            .get()
+            .ok_or(anyhow::anyhow!("failed to get the resource dir"))?,
     };
-    Err(anyhow::anyhow!("failed to get the resource dir"))
+    let res_dir = resource_dir(package_info, &Env::default())
+        .ok_or(anyhow::anyhow!("failed to get the resource dir"))?
+        .join("resources");
+    Ok(res_dir)
 }
 
 /// profiles dir
@@ -88,6 +97,26 @@ pub fn profiles_path() -> Result<PathBuf> {
     Ok(app_home_dir()?.join(PROFILE_YAML))
 }
 
+pub fn traffic_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(TRAFFIC_JSON))
+}
+
+pub fn core_pid_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(CORE_PID))
+}
+
+/// the token of the scheme endpoint, regenerated on every launch
+pub fn scheme_token_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(SCHEME_TOKEN))
+}
+
+/// the runtime config and the pid file of the core instances
+pub fn instances_dir() -> Result<PathBuf> {
+    let dir = app_home_dir()?.join("instances");
+    let _ = std::fs::create_dir_all(&dir);
+    Ok(dir)
+}
+
 #[cfg(not(target_os = "windows"))]
 pub fn service_path() -> Result<PathBuf> {
     Ok(app_resources_dir()?.join("clash-verge-service"))
@@ -120,6 +149,20 @@ pub fn service_log_file() -> Result<PathBuf> {
     Ok(log_file)
 }
 
+pub fn crash_report_file() -> Result<PathBuf> {
+    use chrono::Local;
+
+    let log_dir = app_logs_dir()?.join("crash");
+
+    let local_time = Local::now().format("%Y-%m-%d-%H%M%S").to_string();
+    let log_file = format!("{}.log", local_time);
+    let log_file = log_dir.join(log_file);
+
+    let _ = std::fs::create_dir_all(&log_dir);
+
+    Ok(log_file)
+}
+
 pub fn path_to_str(path: &PathBuf) -> Result<&str> {
     let path_str = path
         .as_os_str()
@@ -1,7 +1,10 @@
+pub mod cli;
+pub mod control;
 pub mod dirs;
 pub mod help;
 pub mod init;
 pub mod resolve;
+pub mod scheme;
 pub mod server;
 pub mod tmpl;
-pub mod unix_helper;
\ No newline at end of file
+pub mod unix_helper;
@@ -1,8 +1,9 @@
 use super::dirs::APP_ID;
-use crate::config::PrfOption;
+use super::scheme::SchemeImport;
 use crate::{
-    config::{Config, PrfItem},
+    config::{Config, DuplicateMode, PrfItem},
     core::*,
+    feat,
     utils::init,
     utils::server,
 };
@@ -10,6 +11,7 @@ use crate::{log_err, trace_err};
 use anyhow::Result;
 use once_cell::sync::OnceCell;
 use std::net::TcpListener;
+use tauri::api::dialog::{blocking::MessageDialogBuilder, MessageDialogButtons, MessageDialogKind};
 use tauri::api::notification;
 use tauri::{AppHandle, CloseRequestApi, Manager};
 
@@ -49,9 +51,14 @@ pub async fn resolve_setup(app_handle: &AppHandle) {
     log::trace!("launch core");
     log_err!(CoreManager::global().init());
 
+    log::trace!("subscribe the core streams");
+    monitor::Monitor::global().init();
+    failover::Failover::global().init();
+    log_err!(traffic::TrafficStats::global().init());
+
     // setup a simple http server for singleton
     log::trace!("launch embed server");
-    server::embed_server(app_handle.app_handle());
+    server::embed_server(Some(app_handle.app_handle()));
 
     let enable_tray = Config::verge().latest().enable_tray.unwrap_or(true);
     if enable_tray {
@@ -75,10 +82,60 @@ pub async fn resolve_setup(app_handle: &AppHandle) {
     }
 }
 
+/// 无窗口运行，没有托盘和快捷键，通过 embed server 控制
+/// 收到退出信号后返回，之后需要调用 `resolve_reset`
+pub async fn resolve_headless(version: String) {
+    VERSION.get_or_init(|| version);
+
+    log_err!(init::init_resources());
+    log_err!(init::startup_script());
+
+    log::trace!("init config");
+    log_err!(Config::init_config());
+
+    log::trace!("init webdav config");
+    log_err!(backup::WebDav::global().init().await);
+
+    log::trace!("launch core");
+    log_err!(CoreManager::global().init());
+
+    log::trace!("subscribe the core streams");
+    monitor::Monitor::global().init();
+    failover::Failover::global().init();
+    log_err!(traffic::TrafficStats::global().init());
+
+    log::trace!("launch embed server");
+    server::embed_server(None);
+
+    log_err!(sysopt::Sysopt::global().init_sysproxy());
+    log_err!(timer::Timer::global().init());
+
+    log::info!(target: "app", "running in headless mode");
+    wait_for_shutdown().await;
+    log::info!(target: "app", "exit the headless mode");
+}
+
+/// ctrl-c, or SIGTERM from systemd
+async fn wait_for_shutdown() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
 /// reset system proxy
 pub fn resolve_reset() {
     log_err!(sysopt::Sysopt::global().reset_sysproxy());
     log_err!(CoreManager::global().stop_core());
+    log_err!(traffic::TrafficStats::global().save());
 }
 
 /// create main window
@@ -217,32 +274,77 @@ pub fn save_window_size_position(app_handle: &AppHandle, save_to_file: bool) ->
 }
 
 pub async fn resolve_scheme(param: String) {
-    let url = param
-        .trim_start_matches("clash://install-config/?url=")
-        .trim_start_matches("clash://install-config?url=");
-    let option = PrfOption {
-        user_agent: None,
-        with_proxy: Some(true),
-        self_proxy: None,
-        danger_accept_invalid_certs: None,
-        update_interval: None,
+    if let Err(err) = import_from_scheme(&param).await {
+        log::error!(target: "app", "failed to import from the scheme: {err}");
+        notice_scheme(format!("Import profile failed: {err}"));
+    }
+}
+
+/// 订阅链接已经存在时询问是否更新，不重复导入
+async fn import_from_scheme(param: &str) -> Result<()> {
+    let import = SchemeImport::parse(param)?;
+    let existing = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        profiles
+            .get_item_by_url(&import.url)
+            .and_then(|item| Some((item.uid.clone()?, item.name.clone())))
     };
-    if let Ok(item) = PrfItem::from_url(url, None, None, Some(option)).await {
-        if Config::profiles().data().append_item(item).is_ok() {
-            notification::Notification::new(APP_ID)
-                .title("Clash Verge")
-                .body("Import profile success")
-                .show()
-                .unwrap();
-        };
-    } else {
-        notification::Notification::new(APP_ID)
-            .title("Clash Verge")
-            .body("Import profile failed")
-            .show()
-            .unwrap();
-        log::error!("failed to parse url: {}", url);
+
+    match existing {
+        Some((uid, name)) => {
+            let name = name.unwrap_or(uid.clone());
+            let message = format!(
+                "The subscription already exists as \"{name}\", update it instead?\n\n{}",
+                import.url
+            );
+            if !confirm_scheme("Update Profile", message).await {
+                log::info!(target: "app", "the update from the scheme is cancelled");
+                return Ok(());
+            }
+            feat::update_profile(uid, Some(import.option)).await?;
+            notice_scheme("Update profile success");
+        }
+        None => {
+            let name = import.name.as_deref().unwrap_or("Remote File");
+            let message = format!(
+                "Import the profile \"{name}\" from the link?\n\n{}",
+                import.url
+            );
+            if !confirm_scheme("Import Profile", message).await {
+                log::info!(target: "app", "the import from the scheme is cancelled");
+                return Ok(());
+            }
+            let item =
+                PrfItem::from_url(&import.url, import.name, None, Some(import.option)).await?;
+            feat::import_profile(item, DuplicateMode::Merge).await?;
+            notice_scheme("Import profile success");
+        }
     }
+    Ok(())
+}
+
+/// 从 scheme 导入订阅前让用户确认，无窗口模式没法确认就不导入
+async fn confirm_scheme(title: &'static str, message: String) -> bool {
+    if handle::Handle::global().app_handle.lock().is_none() {
+        log::warn!(target: "app", "no window to confirm the import, use the control api instead");
+        return false;
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        MessageDialogBuilder::new(title, message)
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::OkCancel)
+            .show()
+    })
+    .await
+    .unwrap_or(false)
+}
+
+fn notice_scheme<S: Into<String>>(body: S) {
+    log_err!(notification::Notification::new(APP_ID)
+        .title("Clash Verge")
+        .body(body)
+        .show());
 }
 
 pub fn handle_window_close(api: CloseRequestApi, app_handle: &AppHandle) {
@@ -0,0 +1,83 @@
+use crate::config::PrfOption;
+use anyhow::{bail, Context, Result};
+use reqwest::Url;
+
+/// `clash://install-config?url=<encoded url>&name=<name>&update-interval=<minutes>&user-agent=<ua>`
+/// the host may end with `/` like `clash://install-config/?url=`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemeImport {
+    /// the subscription url, http or https
+    pub url: String,
+    pub name: Option<String>,
+    pub option: PrfOption,
+}
+
+impl SchemeImport {
+    pub fn parse(param: &str) -> Result<Self> {
+        let link = Url::parse(param.trim()).context("malformed link")?;
+        if link.scheme() != "clash" {
+            bail!("unsupported scheme \"{}\"", link.scheme());
+        }
+        if link.host_str() != Some("install-config") || !matches!(link.path(), "" | "/") {
+            bail!("unsupported action, expect \"install-config\"");
+        }
+
+        let mut url = None;
+        let mut name = None;
+        let mut option = PrfOption::default();
+        // query_pairs 会解码 url 编码的参数
+        for (key, value) in link.query_pairs() {
+            let value = value.trim().to_string();
+            match key.as_ref() {
+                "url" => url = Some(value),
+                "name" if !value.is_empty() => name = Some(value),
+                "update-interval" => {
+                    let interval = value
+                        .parse::<u64>()
+                        .with_context(|| format!("invalid update interval \"{value}\""))?;
+                    option.update_interval = Some(interval);
+                }
+                "user-agent" if !value.is_empty() => option.user_agent = Some(value),
+                _ => log::debug!(target: "app", "ignore the scheme param \"{key}\""),
+            }
+        }
+
+        let url = url.context("the subscription url is missing")?;
+        let subscription = Url::parse(&url).context("malformed subscription url")?;
+        if !matches!(subscription.scheme(), "http" | "https") {
+            bail!("the subscription url must be http or https");
+        }
+
+        Ok(Self { url, name, option })
+    }
+}
+
+#[test]
+fn test_parse_scheme() {
+    let import = SchemeImport::parse(
+        "clash://install-config?url=https%3A%2F%2Fexample.com%2Fsub%3Ftoken%3Da%26flag%3Dclash&name=My%20Sub&update-interval=60&user-agent=clash.meta",
+    )
+    .unwrap();
+    assert_eq!(import.url, "https://example.com/sub?token=a&flag=clash");
+    assert_eq!(import.name.as_deref(), Some("My Sub"));
+    assert_eq!(import.option.update_interval, Some(60));
+    assert_eq!(import.option.user_agent.as_deref(), Some("clash.meta"));
+    assert_eq!(import.option.with_proxy, None);
+
+    let plain = SchemeImport::parse("clash://install-config/?url=https://example.com/sub").unwrap();
+    assert_eq!(plain.url, "https://example.com/sub");
+    assert_eq!(plain.name, None);
+    assert_eq!(plain.option, PrfOption::default());
+
+    // malformed
+    assert!(SchemeImport::parse("clash:/%%").is_err());
+    assert!(SchemeImport::parse("https://install-config?url=https://example.com").is_err());
+    assert!(SchemeImport::parse("clash://other?url=https://example.com").is_err());
+    assert!(SchemeImport::parse("clash://install-config?name=sub").is_err());
+    assert!(SchemeImport::parse("clash://install-config?url=file:///etc/passwd").is_err());
+    assert!(SchemeImport::parse("clash://install-config?url=not%20a%20url").is_err());
+    assert!(SchemeImport::parse(
+        "clash://install-config?url=https://example.com&update-interval=abc"
+    )
+    .is_err());
+}
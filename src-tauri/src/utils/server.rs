@@ -1,18 +1,37 @@
 extern crate warp;
 
-use super::resolve;
+use super::{
+    cli::{self, CliCommand},
+    control, dirs, resolve,
+};
 use crate::config::{Config, IVerge, DEFAULT_PAC};
 use anyhow::{bail, Result};
+use nanoid::nanoid;
+use once_cell::sync::OnceCell;
 use port_scanner::local_port_available;
-use std::convert::Infallible;
+use std::{convert::Infallible, fs, path::Path};
 use tauri::AppHandle;
-use warp::Filter;
+use warp::{http::StatusCode, Filter};
+
+/// the header of the per-run token, sent by the other instance of the app
+const SCHEME_TOKEN_HEADER: &str = "x-verge-scheme-token";
+
+/// the token of this run, also saved in `scheme.token` for the other instances
+static SCHEME_TOKEN: OnceCell<String> = OnceCell::new();
 
 #[derive(serde::Deserialize, Debug)]
 struct QueryParam {
     param: String,
 }
 
+/// the headers of the scheme request
+#[derive(Debug, Default, Clone)]
+struct SchemeRequest {
+    host: Option<String>,
+    origin: Option<String>,
+    token: Option<String>,
+}
+
 /// check whether there is already exists
 pub fn check_singleton() -> Result<()> {
     let port = IVerge::get_singleton_port();
@@ -29,12 +48,15 @@ pub fn check_singleton() -> Result<()> {
                 if argvs.len() > 1 {
                     let param = argvs[1].as_str();
                     if param.starts_with("clash:") {
-                        reqwest::get(format!(
-                            "http://127.0.0.1:{port}/commands/scheme?param={param}"
-                        ))
-                        .await?
-                        .text()
-                        .await?;
+                        let _ = dirs::init_portable_flag();
+                        let token = fs::read_to_string(dirs::scheme_token_path()?)?;
+                        reqwest::Client::new()
+                            .get(format!("http://127.0.0.1:{port}/commands/scheme"))
+                            .header(SCHEME_TOKEN_HEADER, token.trim())
+                            .query(&[("param", param)])
+                            .send()
+                            .await?
+                            .error_for_status()?;
                     }
                 } else {
                     reqwest::get(format!("http://127.0.0.1:{port}/commands/visible"))
@@ -53,16 +75,21 @@ pub fn check_singleton() -> Result<()> {
     }
 }
 
-/// The embed server only be used to implement singleton process
-/// maybe it can be used as pac server later
-pub fn embed_server(app_handle: AppHandle) {
+/// The embed server is used to implement singleton process,
+/// serve the pac file and the control api in `control`
+/// the app handle is none in the headless mode
+pub fn embed_server(app_handle: Option<AppHandle>) {
     let port = IVerge::get_singleton_port();
+    crate::log_err!(control::init_token());
+    crate::log_err!(init_scheme_token());
 
     tauri::async_runtime::spawn(async move {
         let ping = warp::path!("commands" / "ping").map(move || "ok");
 
         let visible = warp::path!("commands" / "visible").map(move || {
-            resolve::create_window(&app_handle);
+            if let Some(app_handle) = app_handle.as_ref() {
+                resolve::create_window(app_handle);
+            }
             "ok"
         });
 
@@ -80,14 +107,164 @@ pub fn embed_server(app_handle: AppHandle) {
                 .unwrap_or_default()
         });
         let scheme = warp::path!("commands" / "scheme")
+            .and(scheme_guard(port))
             .and(warp::query::<QueryParam>())
             .and_then(scheme_handler);
 
         async fn scheme_handler(query: QueryParam) -> Result<impl warp::Reply, Infallible> {
-            resolve::resolve_scheme(query.param).await;
-            Ok("ok")
+            if !query.param.starts_with("clash://") {
+                return Ok(warp::reply::with_status(
+                    "invalid scheme",
+                    StatusCode::BAD_REQUEST,
+                ));
+            }
+            // 导入前需要用户确认，不阻塞另一个实例
+            tauri::async_runtime::spawn(resolve::resolve_scheme(query.param));
+            Ok(warp::reply::with_status("ok", StatusCode::OK))
         }
-        let commands = ping.or(visible).or(pac).or(scheme);
+        let cli = warp::path!("commands" / "cli")
+            .and(warp::post())
+            .and(control::authorized())
+            .and(warp::body::json())
+            .and_then(cli_handler);
+
+        async fn cli_handler(command: CliCommand) -> Result<impl warp::Reply, Infallible> {
+            let reply = match cli::execute(command, true).await {
+                Ok(output) => warp::reply::with_status(output, StatusCode::OK),
+                Err(err) => warp::reply::with_status(format!("{err}"), StatusCode::BAD_REQUEST),
+            };
+            Ok(reply)
+        }
+        let commands = ping
+            .or(visible)
+            .or(pac)
+            .or(scheme)
+            .or(cli)
+            .or(control::routes())
+            .recover(control::handle_rejection);
         warp::serve(commands).run(([127, 0, 0, 1], port)).await;
     });
 }
+
+/// reject the scheme request not sent by the other instance
+fn scheme_guard(port: u16) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("host")
+        .and(warp::header::optional::<String>("origin"))
+        .and(warp::header::optional::<String>(SCHEME_TOKEN_HEADER))
+        .and_then(move |host, origin, token| async move {
+            let request = SchemeRequest {
+                host,
+                origin,
+                token,
+            };
+            check_scheme_request(&request, SCHEME_TOKEN.get(), port).map_err(|err| {
+                log::warn!(target: "app", "reject the scheme request: {err}");
+                warp::reject::custom(control::Forbidden(err.to_string()))
+            })
+        })
+        .untuple_one()
+}
+
+/// generate the token of this run and save it for the other instances
+fn init_scheme_token() -> Result<()> {
+    let token = SCHEME_TOKEN.get_or_init(|| nanoid!(32));
+    save_private(&dirs::scheme_token_path()?, token)
+}
+
+/// only readable by the current user
+fn save_private(path: &Path, content: &str) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(content.as_bytes())?;
+    }
+    #[cfg(not(unix))]
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// only the other instance of the app can call the scheme endpoint,
+/// not the web pages, the rebound domains or the processes without the token
+fn check_scheme_request(request: &SchemeRequest, token: Option<&String>, port: u16) -> Result<()> {
+    // 浏览器的跨域请求会带上 origin
+    if let Some(origin) = request.origin.as_ref() {
+        bail!("cross origin request from {origin}");
+    }
+    // dns rebinding 的 host 是别的域名
+    let hosts = [format!("127.0.0.1:{port}"), format!("localhost:{port}")];
+    match request.host.as_ref() {
+        Some(host) if hosts.contains(host) => {}
+        host => bail!("invalid host {host:?}"),
+    }
+    match (request.token.as_deref(), token) {
+        (Some(given), Some(token)) if control::token_eq(given, token) => Ok(()),
+        _ => bail!("invalid scheme token"),
+    }
+}
+
+#[test]
+fn test_check_scheme_request() {
+    let token = "abcdef".to_string();
+    let valid = SchemeRequest {
+        host: Some("127.0.0.1:33331".into()),
+        origin: None,
+        token: Some("abcdef".into()),
+    };
+    assert!(check_scheme_request(&valid, Some(&token), 33331).is_ok());
+
+    let localhost = SchemeRequest {
+        host: Some("localhost:33331".into()),
+        ..valid.clone()
+    };
+    assert!(check_scheme_request(&localhost, Some(&token), 33331).is_ok());
+
+    // a web page
+    let cross_origin = SchemeRequest {
+        origin: Some("https://example.com".into()),
+        ..valid.clone()
+    };
+    assert!(check_scheme_request(&cross_origin, Some(&token), 33331).is_err());
+
+    // dns rebinding
+    let rebinding = SchemeRequest {
+        host: Some("evil.example.com:33331".into()),
+        ..valid.clone()
+    };
+    assert!(check_scheme_request(&rebinding, Some(&token), 33331).is_err());
+
+    let other_port = SchemeRequest {
+        host: Some("127.0.0.1:8080".into()),
+        ..valid.clone()
+    };
+    assert!(check_scheme_request(&other_port, Some(&token), 33331).is_err());
+
+    let no_host = SchemeRequest {
+        host: None,
+        ..valid.clone()
+    };
+    assert!(check_scheme_request(&no_host, Some(&token), 33331).is_err());
+
+    // a local process without the token
+    let no_token = SchemeRequest {
+        token: None,
+        ..valid.clone()
+    };
+    assert!(check_scheme_request(&no_token, Some(&token), 33331).is_err());
+
+    let wrong_token = SchemeRequest {
+        token: Some("abcdeg".into()),
+        ..valid.clone()
+    };
+    assert!(check_scheme_request(&wrong_token, Some(&token), 33331).is_err());
+
+    // the token is not generated
+    assert!(check_scheme_request(&valid, None, 33331).is_err());
+}
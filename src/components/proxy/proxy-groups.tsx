@@ -1,14 +1,8 @@
 import { ProxyGroupSidebar } from "@/components/proxy/proxy-group-sidebar";
 import { ProxyRender } from "@/components/proxy/proxy-render";
-import { useProfiles } from "@/hooks/use-profiles";
 import { useVerge } from "@/hooks/use-verge";
-import {
-  deleteConnection,
-  getConnections,
-  getGroupProxyDelays,
-  providerHealthCheck,
-  updateProxy,
-} from "@/services/api";
+import { getGroupProxyDelays, providerHealthCheck } from "@/services/api";
+import { selectProxy } from "@/services/cmds";
 import delayManager from "@/services/delay";
 import { cn } from "@/utils";
 import { ChevronRight } from "@mui/icons-material";
@@ -33,7 +27,6 @@ export const ProxyGroups = (props: Props) => {
   const { renderList, onProxies, onHeadState } = useRenderList(mode);
 
   const { verge } = useVerge();
-  const { current, patchCurrent } = useProfiles();
   const timeout = verge?.default_latency_timeout || 5000;
 
   const virtuosoRef = useRef<VirtuosoHandle>(null);
@@ -45,35 +38,10 @@ export const ProxyGroups = (props: Props) => {
     useLockFn(async (group: IProxyGroupItem, proxy: IProxyItem) => {
       if (!["Selector", "URLTest", "Fallback"].includes(group.type)) return;
 
-      const { name, now } = group;
-      await updateProxy(name, proxy.name);
+      const { name } = group;
+      // 后端会保存到 selected 中，并根据 auto_close_connection 断开连接
+      await selectProxy(name, proxy.name);
       onProxies();
-
-      // 断开连接
-      if (verge?.auto_close_connection) {
-        getConnections().then(({ connections }) => {
-          connections.forEach((conn) => {
-            if (conn.chains.includes(now!)) {
-              deleteConnection(conn.id);
-            }
-          });
-        });
-      }
-
-      // 保存到 selected 中
-      if (!current) return;
-      if (!current.selected) current.selected = [];
-
-      const index = current.selected.findIndex(
-        (item) => item.name === group.name,
-      );
-
-      if (index < 0) {
-        current.selected.push({ name, now: proxy.name });
-      } else {
-        current.selected[index] = { name, now: proxy.name };
-      }
-      await patchCurrent({ selected: current.selected });
     }),
   );
 
@@ -5,6 +5,7 @@ import {
   changeClashCore,
   grantPermission,
   restartSidecar,
+  updateCore,
 } from "@/services/cmds";
 import getSystem from "@/utils/get-system";
 import { RestartAlt, SwitchAccessShortcut } from "@mui/icons-material";
@@ -47,7 +48,18 @@ export const ClashCoreViewer = forwardRef<DialogRef, Props>((props, ref) => {
     close: () => setOpen(false),
   }));
 
-  const { clash_core = "verge-mihomo" } = verge ?? {};
+  const {
+    clash_core = "verge-mihomo",
+    custom_cores = [],
+    core_update_url,
+  } = verge ?? {};
+  const coreList = [
+    ...VALID_CORE,
+    ...custom_cores.map((each) => ({
+      name: each.version ? `${each.name} (${each.version})` : each.name,
+      core: each.name,
+    })),
+  ];
 
   const onCoreChange = useLockFn(async (core: string) => {
     if (core === clash_core) return;
@@ -92,6 +104,20 @@ export const ClashCoreViewer = forwardRef<DialogRef, Props>((props, ref) => {
   });
 
   const onUpgrade = useLockFn(async () => {
+    // 配置了下载地址就由后端下载校验后替换
+    if (core_update_url) {
+      try {
+        setUpgrading(true);
+        const version = await updateCore();
+        mutate("getVersion");
+        Notice.success(`${t("Core Version Updated")}: ${version.version}`, 1000);
+      } catch (err: any) {
+        Notice.error(err?.message || err.toString());
+      } finally {
+        setUpgrading(false);
+      }
+      return;
+    }
     try {
       setUpgrading(true);
       await upgradeCore();
@@ -139,7 +165,7 @@ export const ClashCoreViewer = forwardRef<DialogRef, Props>((props, ref) => {
       onClose={() => setOpen(false)}
       onCancel={() => setOpen(false)}>
       <List component="nav">
-        {VALID_CORE.map((each) => (
+        {coreList.map((each) => (
           <ListItemButton
             key={each.core}
             selected={each.core === clash_core}
@@ -22,6 +22,8 @@ export const MiscViewer = forwardRef<DialogRef>((props, ref) => {
     autoCloseConnection: true,
     autoCheckUpdate: true,
     enableBuiltinEnhanced: true,
+    enableKeepKeyOrder: false,
+    enableStrictKeyCase: false,
     proxyLayoutColumn: 6,
     defaultLatencyTest: "",
     autoLogClean: 0,
@@ -36,6 +38,8 @@ export const MiscViewer = forwardRef<DialogRef>((props, ref) => {
         autoCloseConnection: verge?.auto_close_connection ?? true,
         autoCheckUpdate: verge?.auto_check_update ?? true,
         enableBuiltinEnhanced: verge?.enable_builtin_enhanced ?? true,
+        enableKeepKeyOrder: verge?.enable_keep_key_order ?? false,
+        enableStrictKeyCase: verge?.enable_strict_key_case ?? false,
         proxyLayoutColumn: verge?.proxy_layout_column || 6,
         defaultLatencyTest: verge?.default_latency_test || "",
         autoLogClean: verge?.auto_log_clean || 0,
@@ -52,6 +56,8 @@ export const MiscViewer = forwardRef<DialogRef>((props, ref) => {
         auto_close_connection: values.autoCloseConnection,
         auto_check_update: values.autoCheckUpdate,
         enable_builtin_enhanced: values.enableBuiltinEnhanced,
+        enable_keep_key_order: values.enableKeepKeyOrder,
+        enable_strict_key_case: values.enableStrictKeyCase,
         proxy_layout_column: values.proxyLayoutColumn,
         default_latency_test: values.defaultLatencyTest,
         default_latency_timeout: values.defaultLatencyTimeout || 5000,
@@ -127,6 +133,28 @@ export const MiscViewer = forwardRef<DialogRef>((props, ref) => {
           />
         </ListItem>
 
+        <ListItem sx={{ padding: "5px 2px" }}>
+          <ListItemText primary={t("Keep Profile Key Order")} />
+          <SwitchLovely
+            edge="end"
+            checked={values.enableKeepKeyOrder}
+            onChange={(_, c) =>
+              setValues((v) => ({ ...v, enableKeepKeyOrder: c }))
+            }
+          />
+        </ListItem>
+
+        <ListItem sx={{ padding: "5px 2px" }}>
+          <ListItemText primary={t("Strict Config Key Case")} />
+          <SwitchLovely
+            edge="end"
+            checked={values.enableStrictKeyCase}
+            onChange={(_, c) =>
+              setValues((v) => ({ ...v, enableStrictKeyCase: c }))
+            }
+          />
+        </ListItem>
+
         <ListItem sx={{ padding: "5px 2px" }}>
           <ListItemText primary={t("Proxy Layout Column")} />
           <Select
@@ -1,8 +1,9 @@
-import { getProxies, updateProxy } from "@/services/api";
+import { getProxies } from "@/services/api";
 import {
   getProfiles,
   patchProfile,
   patchProfilesConfig,
+  restoreSelected,
 } from "@/services/cmds";
 import useSWR, { mutate } from "swr";
 
@@ -26,41 +27,8 @@ export const useProfiles = () => {
 
   // 根据 selected 的节点选择
   const activateSelected = async () => {
-    const proxiesData = await getProxies();
-    const profileData = await getProfiles();
-
-    if (!profileData || !proxiesData) return;
-
-    const current = profileData.items?.find(
-      (e) => e && e.uid === profileData.current,
-    );
-
-    if (!current) return;
-
-    // init selected array
-    const { selected = [] } = current;
-    const selectedMap = Object.fromEntries(
-      selected.map((each) => [each.name!, each.now!]),
-    );
-
-    let hasChange = false;
-
-    const newSelected: typeof selected = [];
-    const { global, groups } = proxiesData;
-
-    [global, ...groups].forEach(({ type, name, now }) => {
-      if (!now || type !== "Selector") return;
-      if (selectedMap[name] != null && selectedMap[name] !== now) {
-        hasChange = true;
-        updateProxy(name, selectedMap[name]);
-      }
-      newSelected.push({ name, now: selectedMap[name] });
-    });
-
-    if (hasChange) {
-      patchProfile(profileData.current!, { selected: newSelected });
-      mutate("getProxies", getProxies());
-    }
+    await restoreSelected();
+    mutate("getProxies", getProxies());
   };
 
   return {
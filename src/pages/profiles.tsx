@@ -87,12 +87,7 @@ const ProfilePage = () => {
     },
   );
   const [loading, setLoading] = useState(false);
-  const {
-    profiles = {},
-    activateSelected,
-    patchProfiles,
-    mutateProfiles,
-  } = useProfiles();
+  const { profiles = {}, patchProfiles, mutateProfiles } = useProfiles();
 
   const { data: chainLogs = {}, mutate: mutateLogs } = useSWR(
     "getRuntimeLogs",
@@ -263,7 +258,6 @@ const ProfilePage = () => {
           const current = remoteItem.uid;
           patchProfiles({ current });
           mutateLogs();
-          setTimeout(() => activateSelected(), 2000);
         }
       });
     } catch (err: any) {
@@ -288,7 +282,6 @@ const ProfilePage = () => {
         await patchProfiles({ current });
         mutateLogs();
         closeAllConnections();
-        setTimeout(() => activateSelected(), 2000);
         Notice.success(t("Profile Switched"), 1000);
       } catch (err: any) {
         Notice.error(err?.message || err.toString(), 4000);
@@ -8,10 +8,10 @@ export interface MergeResult {
   logs: Record<string, LogMessage[]>;
 }
 
-export async function getClashLogs() {
+export async function getClashLogs(instance?: string) {
   const regex = /time="(.+?)"\s+level=(.+?)\s+msg="(.+?)"/;
   const newRegex = /(.+?)\s+(.+?)\s+(.+)/;
-  const logs = await invoke<string[]>("get_clash_logs");
+  const logs = await invoke<string[]>("get_clash_logs", { instance });
 
   return logs.reduce<ILogItem[]>((acc, log) => {
     const result = log.match(regex);
@@ -31,6 +31,18 @@ export async function getClashLogs() {
   }, []);
 }
 
+export async function getMonitorStats() {
+  return invoke<IMonitorStats>("get_monitor_stats");
+}
+
+export async function getTrafficStats(query: ITrafficQuery) {
+  return invoke<ITrafficRecord[]>("get_traffic_stats", { query });
+}
+
+export async function getMonitorLogs() {
+  return invoke<ICoreLogItem[]>("get_monitor_logs");
+}
+
 export async function getProfiles() {
   return invoke<IProfilesConfig>("get_profiles");
 }
@@ -54,8 +66,9 @@ export async function patchProfilesConfig(profiles: IProfilesConfig) {
 export async function createProfile(
   item: Partial<IProfileItem>,
   fileData?: string | null,
+  duplicate?: IDuplicateMode,
 ) {
-  return invoke<void>("create_profile", { item, fileData });
+  return invoke<string>("create_profile", { item, fileData, duplicate });
 }
 
 export async function viewProfile(index: string) {
@@ -74,13 +87,54 @@ export async function saveProfileFile(index: string, fileData: string) {
   return invoke<void>("save_profile_file", { index, fileData });
 }
 
-export async function importProfile(url: string) {
-  return invoke<void>("import_profile", {
+export type IRuleSection = "rules" | "prepend-rules" | "append-rules";
+
+export async function validateRule(rule: string) {
+  return invoke<IRuleItemConfig>("validate_rule", { rule });
+}
+
+export async function getMergeRules(index: string, section: IRuleSection) {
+  return invoke<IRuleEntry[]>("get_merge_rules", { index, section });
+}
+
+export async function addMergeRule(
+  index: string,
+  section: IRuleSection,
+  rule: string,
+  position?: number,
+) {
+  return invoke<void>("add_merge_rule", { index, section, rule, position });
+}
+
+export async function moveMergeRule(
+  index: string,
+  section: IRuleSection,
+  from: number,
+  to: number,
+) {
+  return invoke<void>("move_merge_rule", { index, section, from, to });
+}
+
+export async function deleteMergeRule(
+  index: string,
+  section: IRuleSection,
+  position: number,
+) {
+  return invoke<void>("delete_merge_rule", { index, section, position });
+}
+
+export async function importProfile(url: string, duplicate?: IDuplicateMode) {
+  return invoke<string>("import_profile", {
     url,
     option: { with_proxy: true },
+    duplicate,
   });
 }
 
+export async function findProfilesByUrl(url: string) {
+  return invoke<IProfileItem[]>("find_profiles_by_url", { url });
+}
+
 export async function reorderProfile(activeId: string, overId: string) {
   return invoke<void>("reorder_profile", {
     activeId,
@@ -214,10 +268,46 @@ export async function changeClashCore(clashCore: string) {
   return invoke<any>("change_clash_core", { clashCore });
 }
 
+export async function getCoreList() {
+  return invoke<ICoreInfo[]>("get_core_list");
+}
+
+export async function registerCore(name: string, path: string) {
+  return invoke<IVergeCore>("register_core", { name, path });
+}
+
+export async function unregisterCore(name: string) {
+  return invoke<void>("unregister_core", { name });
+}
+
+export async function getCoreInstances() {
+  return invoke<IInstanceStatus[]>("get_core_instances");
+}
+
+export async function startCoreInstance(name: string) {
+  return invoke<void>("start_core_instance", { name });
+}
+
+export async function stopCoreInstance(name: string) {
+  return invoke<void>("stop_core_instance", { name });
+}
+
+export async function updateCore(url?: string, sha256?: string) {
+  return invoke<ICoreVersion>("update_core", { url, sha256 });
+}
+
+export async function rollbackCore() {
+  return invoke<void>("rollback_core");
+}
+
 export async function restartSidecar() {
   return invoke<void>("restart_sidecar");
 }
 
+export async function getCoreStatus() {
+  return invoke<ICoreStatus>("get_core_status");
+}
+
 export async function grantPermission(core: string) {
   return invoke<void>("grant_permission", { core });
 }
@@ -253,13 +343,48 @@ export async function cmdGetProxyDelay(
   timeout: number,
   url?: string,
 ) {
-  name = encodeURIComponent(name);
   return invoke<{ message: string; delay: number }>(
     "clash_api_get_proxy_delay",
     { name, url, timeout },
   );
 }
 
+export interface IConnectionFilter {
+  host?: string;
+  process?: string;
+  rule?: string;
+  chain?: string;
+}
+
+export async function getFilteredConnections(filter?: IConnectionFilter) {
+  return invoke<IConnectionsItem[]>("get_connections", { filter });
+}
+
+/// close all the connections if the filter is empty
+export async function closeConnections(filter: IConnectionFilter) {
+  return invoke<number>("close_connections", { filter });
+}
+
+export async function selectProxy(group: string, proxy: string) {
+  return invoke<void>("select_proxy", { group, proxy });
+}
+
+export async function testLatency(group?: string) {
+  return invoke<number>("test_latency", { group });
+}
+
+export async function getLatencyRanking(group?: string) {
+  return invoke<ILatencyStats[]>("get_latency_ranking", { group });
+}
+
+export async function getLatencyHistory(name: string) {
+  return invoke<ILatencyRecord[]>("get_latency_history", { name });
+}
+
+export async function restoreSelected() {
+  return invoke<void>("restore_selected");
+}
+
 export async function cmdTestDelay(url: string) {
   return invoke<number>("test_delay", { url });
 }
@@ -108,6 +108,97 @@ interface ITrafficItem {
   down: number;
 }
 
+interface IMonitorStats {
+  traffic: ITrafficItem;
+  traffic_history: ITrafficItem[];
+  memory: number;
+  connections: number;
+  upload_total: number;
+  download_total: number;
+}
+
+interface ITrafficRecord {
+  date?: string;
+  profile?: string;
+  chains?: string[];
+  process?: string;
+  upload: number;
+  download: number;
+}
+
+interface ITrafficQuery {
+  start?: string;
+  end?: string;
+  group_by?: ("date" | "profile" | "chains" | "process")[];
+}
+
+interface ILatencyRecord {
+  time: number;
+  delay?: number;
+}
+
+interface ILatencyStats {
+  name: string;
+  tested: number;
+  success: number;
+  success_rate: number;
+  avg_delay?: number;
+  jitter?: number;
+  last_delay?: number;
+  last_time?: number;
+}
+
+interface ICoreStatus {
+  state: "stopped" | "running" | "restarting" | "crash-loop";
+  crashes: number;
+  last_crash?: string;
+  crash_report?: string;
+}
+
+interface ICoreVersion {
+  kind: "mihomo" | "clash";
+  version: string;
+  alpha: boolean;
+}
+
+interface ICoreInfo {
+  name: string;
+  path?: string;
+  custom: boolean;
+  version?: ICoreVersion;
+}
+
+interface IVergeCore {
+  name: string;
+  path: string;
+  version?: string;
+}
+
+interface IVergeInstance {
+  name: string;
+  profile: string;
+  mixed_port: number;
+  external_controller: string;
+  secret?: string;
+  allow_lan?: boolean;
+  autostart?: boolean;
+}
+
+interface IInstanceStatus {
+  name: string;
+  profile: string;
+  running: boolean;
+  pid?: number;
+  mixed_port: number;
+  external_controller: string;
+}
+
+interface ICoreLogItem {
+  time: string;
+  log_type: string;
+  payload: string;
+}
+
 interface ILogItem {
   type: string;
   time?: string;
@@ -158,6 +249,11 @@ interface IClashInfo {
   secret?: string;
 }
 
+/**
+ * the same remote profile is merged by default
+ */
+type IDuplicateMode = "merge" | "replace" | "append";
+
 interface IProfileItem {
   uid: string;
   type?: "local" | "remote" | "merge" | "script";
@@ -195,6 +291,20 @@ interface IProfilesConfig {
   items?: IProfileItem[];
 }
 
+interface IRuleItemConfig {
+  rtype: string;
+  payload?: string;
+  rules?: IRuleItemConfig[];
+  target?: string;
+  options?: string[];
+}
+
+interface IRuleEntry {
+  raw: string;
+  rule?: IRuleItemConfig;
+  error?: string;
+}
+
 interface IVergeTestItem {
   uid: string;
   name?: string;
@@ -203,6 +313,7 @@ interface IVergeTestItem {
 }
 
 interface IVergeConfig {
+  control_token?: string;
   app_log_level?: "trace" | "debug" | "info" | "warn" | "error" | string;
   language?: string;
   tray_event?: "main_window" | "system_proxy" | "tun_mode" | string;
@@ -210,6 +321,10 @@ interface IVergeConfig {
   startup_script?: string;
   start_page?: string;
   clash_core?: string;
+  custom_cores?: IVergeCore[];
+  core_instances?: IVergeInstance[];
+  core_update_url?: string;
+  core_update_pubkey?: string;
   theme_mode?: "light" | "dark" | "system";
   traffic_graph?: boolean;
   enable_memory_usage?: boolean;
@@ -276,7 +391,15 @@ interface IVergeConfig {
   auto_check_update?: boolean;
   default_latency_test?: string;
   default_latency_timeout?: number;
+  latency_test_interval?: number;
+  latency_test_group?: string;
+  failover_groups?: string[];
+  failover_check_interval?: number;
+  failover_max_failures?: number;
+  failover_cooldown?: number;
   enable_builtin_enhanced?: boolean;
+  enable_keep_key_order?: boolean;
+  enable_strict_key_case?: boolean;
   auto_log_clean?: 0 | 1 | 2 | 3;
   proxy_layout_column?: number;
   test_list?: IVergeTestItem[];